@@ -10,12 +10,19 @@ use std::process::exit;
 
 use futures::executor;
 use monarch_games::commands::{
-    download_game, get_home_recomendations, get_library, launch_game, move_game_to_monarch,
-    open_store, proton_versions, refresh_library, remove_game, search_games, update_game,
-    update_game_properties, manual_add_game, manual_remove_game
+    cancel_download, download_game, get_download_queue, get_game_details, get_game_log, get_home_recomendations, get_launch_profiles, get_library,
+    get_running_games, launch_game, move_game_to_monarch, open_store, preview_launch, proton_versions,
+    open_game_log, refresh_library, remove_game, reorder_downloads, retry_with_logging,
+    search_games, stop_game, submit_steam_guard_code, tail_game_log, pin_game_manifest, unpin_game, update_all_games, update_game,
+    update_game_properties, manual_add_game,
+    manual_remove_game
 };
+use monarch_games::{download_queue, game_monitor};
 use monarch_library::commands::{
-    create_collection, delete_collection, get_collections, update_collection,
+    add_tags, create_collection, delete_collection, find_duplicate_games, get_collections, get_tags,
+    merge_games, move_collection, remove_tags, reorder_collection_games, reorder_collections,
+    search_library, set_favorite, set_hidden, set_preferred_installation, unmerge_games,
+    update_collection,
 };
 use monarch_utils::commands::{
     async_read_from_pty, async_write_to_pty, clear_cached_images, close_terminal, delete_password,
@@ -73,7 +80,14 @@ fn main() {
             get_library,
             launch_game,
             download_game,
+            get_download_queue,
+            cancel_download,
+            reorder_downloads,
+            submit_steam_guard_code,
             update_game,
+            update_all_games,
+            pin_game_manifest,
+            unpin_game,
             open_store,
             create_collection,
             update_collection,
@@ -100,8 +114,34 @@ fn main() {
             manual_add_game,
             manual_remove_game,
             zoom_window,
+            get_running_games,
+            stop_game,
+            get_game_details,
+            get_launch_profiles,
+            preview_launch,
+            get_game_log,
+            tail_game_log,
+            open_game_log,
+            retry_with_logging,
+            search_library,
+            add_tags,
+            remove_tags,
+            get_tags,
+            set_favorite,
+            set_hidden,
+            reorder_collections,
+            move_collection,
+            reorder_collection_games,
+            find_duplicate_games,
+            merge_games,
+            set_preferred_installation,
+            unmerge_games,
         ])
         .setup(|app| {
+            game_monitor::start(app.handle().clone()); // Starts tracking launched games
+            download_queue::start(app.handle().clone()); // Continues downloads from last session
+            housekeeping::start_update_checks(app.handle().clone()); // Flags games with updates
+
             #[cfg(desktop)]
             {
                 if quicklaunch_is_enabled() {
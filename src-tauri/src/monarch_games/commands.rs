@@ -1,13 +1,17 @@
-use super::monarchgame::MonarchGame;
+use super::download_queue::{self, InstallOptions, QueuedDownload};
+use super::game_logs::{self, LogChunk};
+use super::game_monitor::{self, RunningGame};
+use super::launch_spec::LaunchSpec;
+use super::launch_wrappers::MissingWrappers;
+use super::monarchgame::{LaunchProfile, MonarchGame};
+use super::steamcmd::{self, SteamCmdError};
 use super::{monarch_client, steam_client};
 use anyhow::Result;
-use rand::rng;
-use rand::seq::SliceRandom;
-use serde_json::value::Value;
 use tauri::AppHandle;
 use tracing::{error, info};
 use std::path::PathBuf;
 
+use crate::monarch_library::recommendations::{self, RecommendationSection};
 use crate::monarch_library::{self, games_library};
 use crate::monarch_utils::monarch_vdf::{get_proton_versions, ProtonVersion};
 use crate::monarch_utils::monarch_windows::MiniWindow;
@@ -27,9 +31,9 @@ use super::linux::steam;
 */
 
 #[tauri::command]
-/// Returns MonarchGames from library.json
-pub async fn get_library() -> Result<Value, String> {
-    match games_library::get_games() {
+/// Returns MonarchGames from library.json, hidden games are only included if asked for
+pub async fn get_library(include_hidden: Option<bool>) -> Result<Vec<MonarchGame>, String> {
+    match games_library::get_library(include_hidden.unwrap_or(false)) {
         Ok(games) => Ok(games),
         Err(e) => {
             error!(
@@ -42,24 +46,31 @@ pub async fn get_library() -> Result<Value, String> {
 }
 
 #[tauri::command]
-pub async fn get_home_recomendations() -> Result<Value, String> {
-    match games_library::get_games() {
-        Ok(games) => {
-            let mut games_vec: Vec<MonarchGame> = serde_json::from_value(games.clone()).unwrap();
-            if games_vec.len() > 4 {
-                games_vec.shuffle(&mut rng());
-                let recomended_games: &[MonarchGame] = &games_vec[0..4];
-                Ok(serde_json::to_value(recomended_games).unwrap_or_default())
-            } else {
-                return Ok(games);
-            }
+/// Returns sections of recommended games for the home page
+pub async fn get_home_recomendations() -> Result<Vec<RecommendationSection>, String> {
+    match games_library::get_library(false) {
+        Ok(games) => Ok(recommendations::get_recommendations(&games)),
+        Err(e) => {
+            error!(
+                "monarch_games::commands::get_home_recomendations() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            Err(String::from("Something went wrong getting recommendations!"))
         }
+    }
+}
+
+#[tauri::command]
+/// Returns full details of a library game, such as summary, artwork and genres
+pub async fn get_game_details(id: String) -> Result<MonarchGame, String> {
+    match monarch_client::get_game_details(&id) {
+        Ok(game) => Ok(game),
         Err(e) => {
             error!(
-                "monarch_games::commands::get_library -> {}",
+                "monarch_games::commands::get_game_details() -> {}",
                 e.chain().map(|e| e.to_string()).collect::<String>()
             );
-            Err(String::from("Something went wrong getting library!"))
+            Err(String::from("Something went wrong getting game details!"))
         }
     }
 }
@@ -82,13 +93,20 @@ pub async fn refresh_library() -> Vec<MonarchGame> {
 
 #[tauri::command]
 /// Launch a game
-pub async fn launch_game(handle: AppHandle, mut game: MonarchGame) -> Result<(), String> {
+pub async fn launch_game(
+    handle: AppHandle,
+    game: MonarchGame,
+    profile: Option<String>,
+) -> Result<(), String> {
     info!("Launching game: {}", game.name);
-    if let Err(e) = monarch_client::launch_game(&handle, &mut game).await {
+    if let Err(e) = monarch_client::launch_game(&handle, &game, profile.as_deref()).await {
         error!(
             "monarch_games::commands::launch_game() -> {}",
             e.chain().map(|e| e.to_string()).collect::<String>()
         );
+        if let Some(missing) = e.downcast_ref::<MissingWrappers>() {
+            return Err(format!("Could not launch {}: {missing}", game.name));
+        }
         return Err(format!(
             "Something went wrong while launching: {}",
             game.name
@@ -98,18 +116,143 @@ pub async fn launch_game(handle: AppHandle, mut game: MonarchGame) -> Result<(),
 }
 
 #[tauri::command]
-/// Tells Monarch to download specified game
+/// Launches a game that crashed again, with Proton's logging turned on
+pub async fn retry_with_logging(
+    handle: AppHandle,
+    id: String,
+    profile: Option<String>,
+) -> Result<(), String> {
+    if let Err(e) = monarch_client::retry_with_logging(&handle, &id, profile.as_deref()).await {
+        error!(
+            "monarch_games::commands::retry_with_logging() -> {}",
+            e.chain().map(|e| e.to_string()).collect::<String>()
+        );
+        return Err(String::from("Something went wrong while relaunching game!"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+/// Returns the launch profiles of a game, default profile first
+pub async fn get_launch_profiles(id: String) -> Result<Vec<LaunchProfile>, String> {
+    match monarch_client::get_launch_profiles(&id) {
+        Ok(profiles) => Ok(profiles),
+        Err(e) => {
+            error!(
+                "monarch_games::commands::get_launch_profiles() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            Err(String::from("Something went wrong getting launch profiles!"))
+        }
+    }
+}
+
+#[tauri::command]
+/// Returns the command, environment and working directory a launch would use
+pub async fn preview_launch(id: String, profile: Option<String>) -> Result<LaunchSpec, String> {
+    match monarch_client::preview_launch(&id, profile.as_deref()) {
+        Ok(spec) => Ok(spec),
+        Err(e) => {
+            error!(
+                "monarch_games::commands::preview_launch() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            if let Some(missing) = e.downcast_ref::<MissingWrappers>() {
+                return Err(missing.to_string());
+            }
+            Err(String::from("Something went wrong previewing launch!"))
+        }
+    }
+}
+
+#[tauri::command]
+/// Returns a game's log, or only its last lines if lines is given
+pub async fn get_game_log(id: String, lines: Option<usize>) -> Result<String, String> {
+    match game_logs::read_log(&id, lines) {
+        Ok(log) => Ok(log),
+        Err(e) => {
+            error!(
+                "monarch_games::commands::get_game_log() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            Err(String::from("Something went wrong reading game log!"))
+        }
+    }
+}
+
+#[tauri::command]
+/// Returns what has been written to a game's log since offset, used to follow it
+pub async fn tail_game_log(id: String, offset: u64) -> Result<LogChunk, String> {
+    match game_logs::read_log_from(&id, offset) {
+        Ok(chunk) => Ok(chunk),
+        Err(e) => {
+            error!(
+                "monarch_games::commands::tail_game_log() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            Err(String::from("Something went wrong reading game log!"))
+        }
+    }
+}
+
+#[tauri::command]
+/// Follows a game's log in the Monarch terminal
+pub async fn open_game_log(handle: AppHandle, id: String) -> Result<(), String> {
+    if let Err(e) = game_logs::open_log_in_terminal(&handle, &id).await {
+        error!(
+            "monarch_games::commands::open_game_log() -> {}",
+            e.chain().map(|e| e.to_string()).collect::<String>()
+        );
+        return Err(String::from("Something went wrong opening game log!"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+/// Returns games launched by Monarch that are still running
+pub fn get_running_games() -> Vec<RunningGame> {
+    game_monitor::get_running_games()
+}
+
+#[tauri::command]
+/// Stops a running game, killing it if it doesn't exit in time
+pub async fn stop_game(id: String) -> Result<(), String> {
+    if let Err(e) = game_monitor::stop_game(&id).await {
+        error!(
+            "monarch_games::commands::stop_game() -> {}",
+            e.chain().map(|e| e.to_string()).collect::<String>()
+        );
+        return Err(String::from("Something went wrong while stopping game!"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+/// Tells Monarch to download specified game, into folder if given.
+/// options choose the platform, Proton and branch to install, branch_password is kept in the
+/// keyring.
 pub async fn download_game(
     handle: AppHandle,
     name: String,
     platform: String,
     platform_id: String,
-) -> Result<Vec<MonarchGame>, String> {
+    folder: Option<String>,
+    options: Option<InstallOptions>,
+    branch_password: Option<String>,
+) -> Result<QueuedDownload, String> {
     // For best user experience Monarch downloads all games by itself
     // instead of having to rely on 3rd party launchers.
     info!("Installing: {name}");
-    match monarch_client::download_game(&handle, &name, &platform, &platform_id).await {
-        Ok(new_library) => Ok(new_library),
+    match monarch_client::download_game(
+        &handle,
+        &name,
+        &platform,
+        &platform_id,
+        folder.as_deref(),
+        options.unwrap_or_default(),
+        branch_password.as_deref(),
+    ) {
+        Ok(download) => Ok(download),
         Err(e) => {
             error!(
                 "monarch_games::commands::download_game() -> {}",
@@ -121,15 +264,24 @@ pub async fn download_game(
 }
 
 #[tauri::command]
-/// Tells Monarch to download specified game
+/// Tells Monarch to update specified game, switching it to branch if given
 pub async fn update_game(
     handle: AppHandle,
     name: String,
     platform: String,
     platform_id: String,
+    branch: Option<String>,
+    branch_password: Option<String>,
 ) -> Result<(), String> {
     info!("Updating: {name}");
-    match monarch_client::update_game(&handle, &platform, &platform_id).await {
+    match monarch_client::update_game(
+        &handle,
+        &name,
+        &platform,
+        &platform_id,
+        branch.as_deref(),
+        branch_password.as_deref(),
+    ) {
         Ok(_) => Ok(()),
         Err(e) => {
             error!(
@@ -141,6 +293,103 @@ pub async fn update_game(
     }
 }
 
+#[tauri::command]
+/// Adds every game with an update available to the download queue
+pub async fn update_all_games(handle: AppHandle) -> Result<Vec<QueuedDownload>, String> {
+    info!("Updating all games with an update available");
+    match monarch_client::update_all_games(&handle) {
+        Ok(downloads) => Ok(downloads),
+        Err(e) => {
+            error!(
+                "monarch_games::commands::update_all_games() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            Err(String::from("Something went wrong while updating games!"))
+        }
+    }
+}
+
+#[tauri::command]
+/// Downloads a manifest of one of a game's depots, to roll the game back to a known build
+pub async fn pin_game_manifest(
+    handle: AppHandle,
+    id: String,
+    depot_id: String,
+    manifest_id: String,
+) -> Result<QueuedDownload, String> {
+    info!("Pinning {id} to manifest {manifest_id} of depot {depot_id}");
+    match monarch_client::pin_manifest(&handle, &id, &depot_id, &manifest_id) {
+        Ok(download) => Ok(download),
+        Err(e) => {
+            error!(
+                "monarch_games::commands::pin_game_manifest() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            Err(String::from("Something went wrong while pinning the game!"))
+        }
+    }
+}
+
+#[tauri::command]
+/// Lets a pinned game be updated by update all again
+pub fn unpin_game(id: String) -> Result<(), String> {
+    match monarch_client::unpin_game(&id) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            error!(
+                "monarch_games::commands::unpin_game() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            Err(String::from("Something went wrong while unpinning the game!"))
+        }
+    }
+}
+
+#[tauri::command]
+/// Returns the download queue, in the order downloads will run
+pub fn get_download_queue() -> Vec<QueuedDownload> {
+    download_queue::get_queue()
+}
+
+#[tauri::command]
+/// Removes a download from the queue, stopping it if it's running
+pub async fn cancel_download(handle: AppHandle, id: String) -> Result<(), String> {
+    if let Err(e) = download_queue::cancel(&handle, &id) {
+        error!(
+            "monarch_games::commands::cancel_download() -> {}",
+            e.chain().map(|e| e.to_string()).collect::<String>()
+        );
+        return Err(String::from("Something went wrong while cancelling download!"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+/// Moves downloads to the front of the queue in the given order
+pub async fn reorder_downloads(handle: AppHandle, ids: Vec<String>) -> Result<(), String> {
+    if let Err(e) = download_queue::reorder(&handle, ids) {
+        error!(
+            "monarch_games::commands::reorder_downloads() -> {}",
+            e.chain().map(|e| e.to_string()).collect::<String>()
+        );
+        return Err(String::from("Something went wrong while reordering downloads!"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+/// Passes a Steam Guard code to SteamCMD after a steam-guard-required event
+pub fn submit_steam_guard_code(code: String) -> Result<(), String> {
+    if let Err(e) = steamcmd::submit_steam_guard_code(&code) {
+        error!(
+            "monarch_games::commands::submit_steam_guard_code() -> {}",
+            e.chain().map(|e| e.to_string()).collect::<String>()
+        );
+        return Err(String::from("Steam isn't waiting for a Steam Guard code!"));
+    }
+    Ok(())
+}
+
 #[tauri::command]
 /// Tells Monarch to remove specified game
 pub async fn remove_game(
@@ -155,6 +404,9 @@ pub async fn remove_game(
             "monarch_games::commands::remove_game() -> {}",
             e.chain().map(|e| e.to_string()).collect::<String>()
         );
+        if let Some(steamcmd_error) = e.downcast_ref::<SteamCmdError>() {
+            return Err(format!("Could not remove {name}: {steamcmd_error}"));
+        }
         return Err(format!("Something went wrong while removing: {name}"));
     }
     Ok(())
@@ -175,11 +427,22 @@ pub async fn move_game_to_monarch(
             "monarch_games::commands::move_game_to_monarch() -> {}",
             e.chain().map(|e| e.to_string()).collect::<String>()
         );
+        if let Some(steamcmd_error) = e.downcast_ref::<SteamCmdError>() {
+            return Err(format!("Could not remove {name}: {steamcmd_error}"));
+        }
         return Err(format!("Something went wrong while removing: {name}"));
     }
 
     // Then reinstall on Monarch
-    if let Err(e) = monarch_client::download_game(&handle, &name, &platform, &platform_id).await {
+    if let Err(e) = monarch_client::download_game(
+        &handle,
+        &name,
+        &platform,
+        &platform_id,
+        None,
+        InstallOptions::default(),
+        None,
+    ) {
         error!(
             "monarch_games::commands::move_game_to_monarch() -> {}",
             e.chain().map(|e| e.to_string()).collect::<String>()
@@ -286,6 +549,17 @@ pub async fn manual_add_game(mut game: MonarchGame) -> Result<(), String> {
         }
     }
 
+    if monarch_fs::is_in_cache_dir(&PathBuf::from(&(game.artwork_path))) {
+        match monarch_fs::copy_cache_to_library(&PathBuf::from(&(game.artwork_path))) {
+            Ok(path) => {
+                game.artwork_path = path.to_str().unwrap().to_string();
+            }
+            Err(e) => {
+                error!("monarch_games::commands::manual_add_game() -> {}", e.chain().map(|e| e.to_string()).collect::<String>());
+            }
+        }
+    }
+
     if let Err(e) = monarch_library::games_library::add_game(&game) {
         error!("monarch_games::commands::manual_add_game() -> {}", e.chain().map(|e| e.to_string()).collect::<String>());
         return Err(format!("Failed to add game: {}", game.name))
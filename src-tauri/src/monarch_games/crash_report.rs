@@ -0,0 +1,168 @@
+/*
+* Crash detection and diagnostics for games launched by Monarch.
+*
+* A game is considered crashed if the process Monarch started exits unsuccessfully, or if the
+* game exits within QUICK_EXIT of being launched. A report with everything needed to figure out
+* what went wrong is written next to the game's log as <id>-crash-<time>.json, and sent to the
+* frontend in a game-crashed event.
+*/
+
+use anyhow::{Context, Result};
+use portable_pty::ExitStatus;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::game_logs;
+use super::launch_spec::LaunchSpec;
+use super::monarchgame::MonarchGame;
+use crate::monarch_library::play_history::unix_now;
+use crate::monarch_utils::monarch_fs::write_json_content;
+use crate::monarch_utils::monarch_logger::get_game_log_path;
+
+/// Games exiting faster than this are considered crashed, in seconds.
+pub const QUICK_EXIT: u64 = 10;
+
+/// Lines of output included in a report.
+const OUTPUT_LINES: usize = 200;
+
+/// Diagnostics of a game that has crashed, as sent to the frontend.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct CrashReport {
+    pub game_id: String,
+    pub game_name: String,
+    pub crashed_at: u64,
+    pub reason: String,
+    pub exit_code: Option<u32>,
+    pub signal: Option<String>,
+    pub session_seconds: u64,
+    pub runner: String, // Compatibility layer used, "native" or the game's platform
+    pub runner_version: String,
+    pub command: String,
+    pub env: BTreeMap<String, String>,
+    pub output: String,     // Last lines of the game's log
+    pub proton_log: String, // Last lines of the Proton log, if the game was launched with logging
+    pub report_path: String,
+}
+
+/// What Monarch knows about a game that has exited, used to build a report.
+pub struct ExitDetails<'a> {
+    pub game: &'a MonarchGame,
+    pub spec: Option<&'a LaunchSpec>,
+    pub status: Option<&'a ExitStatus>,
+    pub session_seconds: u64,
+}
+
+/// Returns why a game is considered crashed, or None if it exited normally.
+pub fn crash_reason(status: Option<&ExitStatus>, session_seconds: u64) -> Option<String> {
+    if let Some(status) = status {
+        if let Some(signal) = status.signal() {
+            return Some(format!("Killed by signal: {signal}"));
+        }
+        if !status.success() {
+            return Some(format!("Exited with code: {}", status.exit_code()));
+        }
+    }
+
+    if session_seconds < QUICK_EXIT {
+        return Some(format!("Exited after only {session_seconds} seconds"));
+    }
+    None
+}
+
+/// Collects diagnostics of a crashed game and writes them to a report file.
+pub fn create_report(details: ExitDetails, reason: String) -> Result<CrashReport> {
+    let game: &MonarchGame = details.game;
+    let (runner, runner_version) = runner_info(game);
+
+    let output: String = game_logs::read_log(&game.id, Some(OUTPUT_LINES))
+        .with_context(|| "crash_report::create_report() -> ")?;
+
+    let mut report: CrashReport = CrashReport {
+        game_id: game.id.clone(),
+        game_name: game.name.clone(),
+        crashed_at: unix_now(),
+        reason,
+        exit_code: details.status.map(|status| status.exit_code()),
+        signal: details.status.and_then(|status| status.signal().map(str::to_string)),
+        session_seconds: details.session_seconds,
+        runner,
+        runner_version,
+        command: details.spec.map(|spec| spec.to_string()).unwrap_or_default(),
+        env: details.spec.map(|spec| spec.env.clone()).unwrap_or_default(),
+        output,
+        proton_log: read_proton_log(&game.id),
+        report_path: String::new(),
+    };
+
+    let path: PathBuf = get_game_log_path(&game.id)
+        .with_file_name(format!("{}-crash-{}.json", game.id, report.crashed_at));
+    report.report_path = path.display().to_string();
+
+    write_json_content(json!(report), &path).with_context(|| "crash_report::create_report() -> ")?;
+    Ok(report)
+}
+
+/// Turns on Proton's own logging for the next launch of a game.
+pub fn enable_logging(game: &mut MonarchGame) {
+    let dir: PathBuf = proton_log_dir(&game.id);
+    if let Err(e) = fs::create_dir_all(&dir) {
+        tracing::warn!("crash_report::enable_logging() Failed to create: {} | Err: {e}", dir.display());
+    }
+
+    game.env.insert(String::from("PROTON_LOG"), String::from("1"));
+    game.env
+        .insert(String::from("PROTON_LOG_DIR"), dir.display().to_string());
+}
+
+/// Folder Proton writes its logs to when launched with logging.
+fn proton_log_dir(id: &str) -> PathBuf {
+    get_game_log_path(id).with_file_name(format!("{id}-proton"))
+}
+
+/// Returns the last lines of the newest Proton log of a game, empty if there is none.
+fn read_proton_log(id: &str) -> String {
+    let newest: Option<PathBuf> = fs::read_dir(proton_log_dir(id))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".log"))
+        .max_by_key(|entry| entry.metadata().and_then(|meta| meta.modified()).ok())
+        .map(|entry| entry.path());
+
+    match newest.and_then(|path| fs::read(path).ok()) {
+        Some(bytes) => game_logs::last_lines(&String::from_utf8_lossy(&bytes), OUTPUT_LINES),
+        None => String::new(),
+    }
+}
+
+/// Returns the runner a game was launched with and its version, if known.
+/// Proton keeps its version in a file called version next to its executable.
+fn runner_info(game: &MonarchGame) -> (String, String) {
+    if game.compatibility.is_empty() {
+        let runner: &str = if game.executable_path.is_empty() {
+            &game.platform
+        } else {
+            "native"
+        };
+        return (runner.to_string(), String::new());
+    }
+
+    let version: String = Path::new(&game.compatibility)
+        .parent()
+        .map(|dir| dir.join("version"))
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|content| {
+            // Formatted as: <build time> <version>
+            let content: &str = content.trim();
+            content
+                .split_once(' ')
+                .map_or(content, |(_, version)| version)
+                .to_string()
+        })
+        .unwrap_or_default();
+
+    (game.compatibility.clone(), version)
+}
@@ -0,0 +1,594 @@
+/*
+* Queue of games to install or update, worked through one at a time.
+*
+* The queue is kept in download_queue.json so that it survives Monarch restarting. A download
+* that was running when Monarch closed is simply started again, as SteamCMD continues from
+* whatever it had already downloaded.
+*
+* Events sent to the frontend:
+*   download-queue     The whole queue, whenever it changes
+*   download-progress  Progress of the active download
+*   download-finished  A download that has finished
+*   download-failed    A download that has failed, with its error set
+*/
+
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use portable_pty::ChildKiller;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+
+use super::monarchgame::MonarchGame;
+use super::steam_appinfo;
+use super::steam_client;
+use super::steamcmd::{InstallTarget, SteamCmdError, UpdateProgress};
+use crate::monarch_library::collections::generate_uuid;
+use crate::monarch_library::games_library;
+use crate::monarch_library::play_history::unix_now;
+use crate::monarch_utils::monarch_fs::{get_download_queue_json_path, path_exists, write_json_content};
+
+static QUEUE: Lazy<Mutex<Vec<QueuedDownload>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// The download being worked on along with its SteamCMD process, used to cancel it.
+struct ActiveDownload {
+    id: String,
+    killer: Option<Box<dyn ChildKiller + Send + Sync>>, // None until SteamCMD has started
+    cancelled: bool, // SteamCMD is killed as soon as it starts, or not started at all
+}
+
+static ACTIVE: Lazy<Mutex<Option<ActiveDownload>>> = Lazy::new(|| Mutex::new(None));
+
+/// Wakes the worker up when something is added to the queue.
+static WAKE: Lazy<Notify> = Lazy::new(Notify::new);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadKind {
+    Install,
+    Update,
+    Pin, // Download a specific manifest of a depot over the installed game
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadState {
+    Queued,
+    Downloading,
+    Failed,
+}
+
+/// A game waiting to be, or being, installed or updated.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QueuedDownload {
+    pub id: String,
+    pub name: String,
+    pub platform: String,
+    pub platform_id: String,
+    pub kind: DownloadKind,
+    pub state: DownloadState,
+    #[serde(default)]
+    pub error: String, // Why the download failed, empty otherwise
+    #[serde(default)]
+    pub progress: f64, // Percent
+    #[serde(flatten)]
+    pub options: InstallOptions,
+    pub added_at: u64,
+}
+
+/// Where and how a game is installed, empty for the defaults.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct InstallOptions {
+    pub install_dir: String,       // Folder to install to, empty for SteamCMD's own
+    pub platform_override: String, // Platform to download the build for, empty for the native one
+    pub compatibility: String,     // Proton to play a build for another platform with
+    pub branch: String,            // Steam branch to install, empty for public
+    pub depot_id: String,          // Depot and manifest to pin, only set for pins
+    pub manifest_id: String,
+}
+
+impl InstallOptions {
+    pub fn target(&self) -> InstallTarget {
+        InstallTarget {
+            install_dir: Some(self.install_dir.clone()).filter(|dir| !dir.is_empty()),
+            platform: Some(self.platform_override.clone()).filter(|p| !p.is_empty()),
+        }
+    }
+}
+
+/// Progress of the active download, as sent to the frontend.
+#[derive(Serialize, Clone, Debug)]
+pub struct DownloadProgress {
+    pub id: String,
+    pub platform_id: String,
+    pub state: String, // What SteamCMD is doing, such as downloading or verifying
+    pub progress: f64, // Percent
+    pub downloaded: u64,
+    pub total: u64,
+    pub speed: u64,        // Bytes per second
+    pub eta: Option<u64>,  // Seconds left, None while unknown
+}
+
+/// Loads the queue from disk and starts working through it on a seperate task.
+pub fn start(handle: AppHandle) {
+    match read_queue() {
+        Ok(mut queue) => {
+            // Downloads that were running when Monarch closed are started over
+            for download in queue.iter_mut() {
+                if download.state == DownloadState::Downloading {
+                    download.state = DownloadState::Queued;
+                }
+            }
+            *lock_queue() = queue;
+        }
+        Err(e) => error!("download_queue::start() -> {e}"),
+    }
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match next_download() {
+                Some(download) => run_download(&handle, download).await,
+                None => WAKE.notified().await,
+            }
+        }
+    });
+}
+
+/// Adds a game to the end of the queue. A game already in the queue isn't added again,
+/// but is queued again with the new kind and options if it had failed.
+/// Errors if the game is already queued or downloading as another kind of download.
+pub fn enqueue(
+    handle: &AppHandle,
+    name: &str,
+    platform: &str,
+    platform_id: &str,
+    kind: DownloadKind,
+    options: InstallOptions,
+) -> Result<QueuedDownload> {
+    let download: QueuedDownload = {
+        let mut queue = lock_queue();
+
+        match queue
+            .iter_mut()
+            .find(|d| d.platform == platform && d.platform_id == platform_id)
+        {
+            Some(existing) => {
+                if existing.state == DownloadState::Failed {
+                    existing.kind = kind;
+                    existing.options = options;
+                    existing.state = DownloadState::Queued;
+                    existing.error = String::new();
+                    existing.progress = 0.0;
+                } else if existing.kind != kind {
+                    bail!(
+                        "download_queue::enqueue() | Err: {name} is already queued as: {:?}",
+                        existing.kind
+                    )
+                }
+                existing.clone()
+            }
+            None => {
+                let download: QueuedDownload = QueuedDownload {
+                    id: generate_uuid(),
+                    name: name.to_string(),
+                    platform: platform.to_string(),
+                    platform_id: platform_id.to_string(),
+                    kind,
+                    state: DownloadState::Queued,
+                    error: String::new(),
+                    progress: 0.0,
+                    options,
+                    added_at: unix_now(),
+                };
+                queue.push(download.clone());
+                download
+            }
+        }
+    };
+
+    queue_changed(handle).with_context(|| "download_queue::enqueue() -> ")?;
+    WAKE.notify_one();
+    Ok(download)
+}
+
+/// Removes a download from the queue, stopping it if it's the active one.
+pub fn cancel(handle: &AppHandle, id: &str) -> Result<()> {
+    {
+        let mut queue = lock_queue();
+        let len: usize = queue.len();
+        queue.retain(|download| download.id != id);
+        if queue.len() == len {
+            bail!("download_queue::cancel() | Err: No download with id: {id}")
+        }
+    }
+
+    cancel_active(id);
+    queue_changed(handle).with_context(|| "download_queue::cancel() -> ")
+}
+
+/// Moves downloads to the front of the queue in the given order,
+/// the rest keep their relative order after them.
+pub fn reorder(handle: &AppHandle, ids: Vec<String>) -> Result<()> {
+    {
+        let mut queue = lock_queue();
+        if let Some(id) = ids.iter().find(|id| !queue.iter().any(|d| d.id == **id)) {
+            bail!("download_queue::reorder() | Err: No download with id: {id}")
+        }
+
+        queue.sort_by_key(|download| {
+            ids.iter()
+                .position(|id| *id == download.id)
+                .unwrap_or(ids.len())
+        });
+    }
+
+    queue_changed(handle).with_context(|| "download_queue::reorder() -> ")
+}
+
+/// Returns every download in the queue, in the order they will run.
+pub fn get_queue() -> Vec<QueuedDownload> {
+    lock_queue().clone()
+}
+
+/// Returns the first queued download and marks it as downloading.
+fn next_download() -> Option<QueuedDownload> {
+    let mut queue = lock_queue();
+    let download: &mut QueuedDownload = queue
+        .iter_mut()
+        .find(|download| download.state == DownloadState::Queued)?;
+
+    download.state = DownloadState::Downloading;
+    download.progress = 0.0;
+
+    // Made active while the queue is locked, so cancelling it can't be missed
+    *lock_active() = Some(ActiveDownload {
+        id: download.id.clone(),
+        killer: None,
+        cancelled: false,
+    });
+    Some(download.clone())
+}
+
+/// Cancels the active download if it has the given id. Its SteamCMD is killed if it's
+/// running, otherwise it's killed as soon as it starts.
+fn cancel_active(id: &str) {
+    let mut active = lock_active();
+    let Some(active) = active.as_mut().filter(|active| active.id == id) else {
+        return;
+    };
+
+    info!("Cancelling active download: {id}");
+    active.cancelled = true;
+    if let Some(killer) = active.killer.as_mut() {
+        if let Err(e) = killer.kill() {
+            warn!("download_queue::cancel_active() Failed to kill SteamCMD! | Err: {e}");
+        }
+    }
+}
+
+/// Keeps the SteamCMD process of the active download to cancel it with,
+/// killing it straight away if the download was cancelled before it started.
+fn set_active_process(id: &str, mut killer: Box<dyn ChildKiller + Send + Sync>) {
+    let mut active = lock_active();
+    let Some(active) = active.as_mut().filter(|active| active.id == id) else {
+        return;
+    };
+
+    if active.cancelled {
+        info!("Download cancelled before SteamCMD started: {id}");
+        if let Err(e) = killer.kill() {
+            warn!("download_queue::set_active_process() Failed to kill SteamCMD! | Err: {e}");
+        }
+    }
+    active.killer = Some(killer);
+}
+
+/// Fails if the active download has been cancelled, checked before starting SteamCMD.
+fn check_cancelled(id: &str) -> Result<()> {
+    if lock_active()
+        .as_ref()
+        .is_some_and(|active| active.id == id && active.cancelled)
+    {
+        bail!("download_queue::check_cancelled() | Err: Download was cancelled: {id}")
+    }
+    Ok(())
+}
+
+/// Runs a download to the end and updates the queue with how it went.
+async fn run_download(handle: &AppHandle, download: QueuedDownload) {
+    info!("Starting download: {}", download.name);
+    if let Err(e) = queue_changed(handle) {
+        error!("download_queue::run_download() -> {e}");
+    }
+
+    let result: Result<()> = download_game(handle, &download).await;
+    *lock_active() = None;
+
+    let still_queued: bool = lock_queue().iter().any(|d| d.id == download.id);
+    if !still_queued {
+        info!("Download cancelled: {}", download.name);
+        return;
+    }
+
+    match result {
+        Ok(()) => {
+            info!("Download finished: {}", download.name);
+            lock_queue().retain(|d| d.id != download.id);
+            emit(handle, "download-finished", &download);
+        }
+        Err(e) => {
+            error!(
+                "download_queue::run_download() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+
+            let mut failed: QueuedDownload = download;
+            failed.state = DownloadState::Failed;
+            // Known SteamCMD failures have messages meant for the user
+            failed.error = match e.downcast_ref::<SteamCmdError>() {
+                Some(steamcmd_error) => steamcmd_error.to_string(),
+                None => e.root_cause().to_string(),
+            };
+            if let Some(queued) = lock_queue().iter_mut().find(|d| d.id == failed.id) {
+                *queued = failed.clone();
+            }
+            emit(handle, "download-failed", &failed);
+        }
+    }
+
+    if let Err(e) = queue_changed(handle) {
+        error!("download_queue::run_download() -> {e}");
+    }
+}
+
+/// Installs or updates a game, adding newly installed games to the library.
+async fn download_game(handle: &AppHandle, download: &QueuedDownload) -> Result<()> {
+    match download.platform.as_str() {
+        "steam" | "steamcmd" => {
+            check_cancelled(&download.id).with_context(|| "download_queue::download_game() -> ")?;
+            if !steam_client::is_installed() {
+                warn!("download_queue::download_game() SteamCMD not found!");
+                info!("Attempting to download and install SteamCMD...");
+
+                steam_client::download_and_install(handle)
+                    .await
+                    .with_context(|| "download_queue::download_game() -> ")?;
+            }
+
+            let (id, platform_id) = (download.id.clone(), download.platform_id.clone());
+            let (kind, options) = (download.kind, download.options.clone());
+            let progress_handle: AppHandle = handle.clone();
+            let mut tracker: SpeedTracker = SpeedTracker::default();
+
+            tauri::async_runtime::spawn_blocking(move || -> Result<()> {
+                // Installing SteamCMD or waiting for the session can take a while
+                check_cancelled(&id)?;
+                let on_spawn = |killer| set_active_process(&id, killer);
+                if kind == DownloadKind::Pin {
+                    return steam_client::download_depot(&progress_handle, &platform_id, &options, on_spawn);
+                }
+
+                steam_client::app_update(
+                    &progress_handle,
+                    &platform_id,
+                    &options.target(),
+                    &options.branch,
+                    on_spawn,
+                    |progress| {
+                        let progress: DownloadProgress = tracker.update(&id, &platform_id, progress);
+                        if let Some(queued) = lock_queue().iter_mut().find(|d| d.id == id) {
+                            queued.progress = progress.progress;
+                        }
+                        emit(&progress_handle, "download-progress", &progress);
+                    },
+                )?;
+
+                // The game is launched from its app info, which may have changed with the update
+                if let Err(e) = steam_client::fetch_app_info(&progress_handle, &platform_id) {
+                    warn!("download_queue::download_game() -> {e:#}");
+                }
+                Ok(())
+            })
+            .await
+            .with_context(|| "download_queue::download_game() SteamCMD task panicked! | Err: ")?
+            .with_context(|| "download_queue::download_game() -> ")?;
+
+            if download.kind == DownloadKind::Install {
+                let mut game: MonarchGame =
+                    steam_client::get_installed_game(&download.platform_id, &download.options)
+                        .await
+                        .with_context(|| "download_queue::download_game() -> ")?;
+
+                // Games without a launch config are resolved again when launched
+                match steam_appinfo::read_cache(&download.platform_id)
+                    .with_context(|| format!("download_queue::download_game() | Err: No app info cached for: {}", download.platform_id))
+                    .and_then(|info| steam_client::resolve_launch(&game, &info))
+                {
+                    Ok(resolved) => game = resolved,
+                    Err(e) => warn!("download_queue::download_game() -> {e:#}"),
+                }
+                games_library::add_game(&game)
+                    .with_context(|| "download_queue::download_game() -> ")?;
+            } else {
+                record_download(download);
+            }
+            Ok(())
+        }
+        platform => bail!("download_queue::download_game() | Err: Can't download games from: {platform}"),
+    }
+}
+
+/// Records the branch and pinned manifests of a game that has been updated or pinned.
+fn record_download(download: &QueuedDownload) {
+    let options: &InstallOptions = &download.options;
+
+    let result: Result<()> = games_library::edit_games(|game| {
+        if game.platform != download.platform || game.platform_id != download.platform_id {
+            return;
+        }
+
+        match download.kind {
+            // Updating moves the game off any manifest it was pinned to
+            DownloadKind::Update => {
+                game.update_available = false;
+                game.beta_branch = options.branch.clone();
+                game.pinned_manifests.clear();
+            }
+            DownloadKind::Pin => {
+                game.pinned_manifests
+                    .insert(options.depot_id.clone(), options.manifest_id.clone());
+            }
+            DownloadKind::Install => {}
+        }
+    });
+    if let Err(e) = result {
+        error!("download_queue::record_download() -> {e}");
+    }
+}
+
+/// Turns progress printed by SteamCMD into progress with speed and time left.
+#[derive(Default)]
+struct SpeedTracker {
+    last: Option<(Instant, u64)>,
+    speed: f64, // Bytes per second, smoothed over several updates
+}
+
+impl SpeedTracker {
+    fn update(&mut self, id: &str, platform_id: &str, progress: UpdateProgress) -> DownloadProgress {
+        let now: Instant = Instant::now();
+
+        match self.last {
+            // SteamCMD starts counting from zero again when it moves on to verifying
+            Some((time, current)) if progress.current >= current => {
+                let seconds: f64 = now.duration_since(time).as_secs_f64();
+                if seconds > 0.0 {
+                    let speed: f64 = (progress.current - current) as f64 / seconds;
+                    self.speed = if self.speed == 0.0 {
+                        speed
+                    } else {
+                        0.3 * speed + 0.7 * self.speed
+                    };
+                }
+            }
+            _ => self.speed = 0.0,
+        }
+        self.last = Some((now, progress.current));
+
+        let eta: Option<u64> = (self.speed > 0.0).then(|| {
+            (progress.total.saturating_sub(progress.current) as f64 / self.speed) as u64
+        });
+
+        DownloadProgress {
+            id: id.to_string(),
+            platform_id: platform_id.to_string(),
+            state: progress.state,
+            progress: progress.progress,
+            downloaded: progress.current,
+            total: progress.total,
+            speed: self.speed as u64,
+            eta,
+        }
+    }
+}
+
+/// Saves the queue and sends it to the frontend.
+fn queue_changed(handle: &AppHandle) -> Result<()> {
+    let queue: Vec<QueuedDownload> = get_queue();
+    emit(handle, "download-queue", &queue);
+
+    let path: PathBuf = get_download_queue_json_path();
+    write_json_content(json!(queue), &path).with_context(|| "download_queue::queue_changed() -> ")
+}
+
+fn read_queue() -> Result<Vec<QueuedDownload>> {
+    let path: PathBuf = get_download_queue_json_path();
+    if !path_exists(&path) {
+        return Ok(Vec::new());
+    }
+
+    let content: String = fs::read_to_string(&path).with_context(|| {
+        format!(
+            "download_queue::read_queue() Error reading: {file} | Err: ",
+            file = path.display()
+        )
+    })?;
+    serde_json::from_str(&content)
+        .with_context(|| "download_queue::read_queue() Failed to parse download queue! | Err: ")
+}
+
+fn emit<S: Serialize + Clone>(handle: &AppHandle, event: &str, payload: &S) {
+    if let Err(e) = handle.emit(event, payload) {
+        error!("download_queue::emit() Failed to emit {event} event! | Err: {e}");
+    }
+}
+
+/// Locks the queue, recovering it if another thread panicked while holding it.
+fn lock_queue() -> MutexGuard<'static, Vec<QueuedDownload>> {
+    QUEUE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn lock_active() -> MutexGuard<'static, Option<ActiveDownload>> {
+    ACTIVE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    /// Stands in for a SteamCMD process, remembering if it was killed.
+    #[derive(Clone, Debug, Default)]
+    struct TestKiller(Arc<AtomicBool>);
+
+    impl ChildKiller for TestKiller {
+        fn kill(&mut self) -> io::Result<()> {
+            self.0.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn cancelling_before_steamcmd_starts_kills_it_when_it_starts() {
+        lock_queue().push(QueuedDownload {
+            id: String::from("cancel-test"),
+            name: String::from("Portal"),
+            platform: String::from("steamcmd"),
+            platform_id: String::from("400"),
+            kind: DownloadKind::Install,
+            state: DownloadState::Queued,
+            error: String::new(),
+            progress: 0.0,
+            options: InstallOptions::default(),
+            added_at: 0,
+        });
+        let download: QueuedDownload = next_download().unwrap();
+        assert!(check_cancelled(&download.id).is_ok());
+
+        // Another download being cancelled changes nothing
+        cancel_active("other-download");
+        assert!(check_cancelled(&download.id).is_ok());
+
+        cancel_active(&download.id);
+        assert!(check_cancelled(&download.id).is_err());
+
+        let killer: TestKiller = TestKiller::default();
+        set_active_process(&download.id, Box::new(killer.clone()));
+        assert!(killer.0.load(Ordering::SeqCst));
+
+        *lock_active() = None;
+        lock_queue().retain(|d| d.id != download.id);
+    }
+}
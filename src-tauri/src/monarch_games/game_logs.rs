@@ -0,0 +1,166 @@
+/*
+* Per-game logs under logs/games/, containing output of the game and its hooks.
+*
+* A log is rotated when a launch starts and it has grown past MAX_LOG_SIZE, keeping the
+* previous KEPT_LOGS logs as <id>.1.log, <id>.2.log and so on.
+*/
+
+use anyhow::{Context, Result};
+use portable_pty::CommandBuilder;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use crate::monarch_library::play_history::unix_now;
+use crate::monarch_utils::monarch_logger::get_game_log_path;
+use crate::monarch_utils::monarch_terminal::run_command_in_terminal;
+
+/// Size a log may grow to before it is rotated.
+const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024;
+
+/// How many rotated logs are kept for each game.
+const KEPT_LOGS: usize = 3;
+
+/// Lines shown when a log is opened in the terminal.
+const TERMINAL_LINES: usize = 200;
+
+/// Part of a log read from an offset, offset is where the next read should start.
+#[derive(Serialize, Clone, Debug)]
+pub struct LogChunk {
+    pub content: String,
+    pub offset: u64,
+}
+
+/// Opens a game's log for appending and writes a header line to it.
+pub fn open_log(id: &str, header: &str) -> Result<File> {
+    let path: PathBuf = get_game_log_path(id);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| {
+            format!(
+                "game_logs::open_log() Failed to create: {dir} | Err: ",
+                dir = dir.display()
+            )
+        })?;
+    }
+
+    let mut file: File = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| {
+            format!(
+                "game_logs::open_log() Failed to open: {file} | Err: ",
+                file = path.display()
+            )
+        })?;
+
+    writeln!(file, "[{}] {header}", unix_now())
+        .with_context(|| "game_logs::open_log() Failed to write to game log! | Err: ")?;
+    Ok(file)
+}
+
+/// Moves a game's log aside if it has grown too large, dropping the oldest one.
+pub fn rotate_log(id: &str) -> Result<()> {
+    let path: PathBuf = get_game_log_path(id);
+    let size: u64 = fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+    if size < MAX_LOG_SIZE {
+        return Ok(());
+    }
+
+    for i in (1..KEPT_LOGS).rev() {
+        let from: PathBuf = rotated_path(&path, i);
+        if from.exists() {
+            fs::rename(&from, rotated_path(&path, i + 1))
+                .with_context(|| "game_logs::rotate_log() Failed to rotate log! | Err: ")?;
+        }
+    }
+    fs::rename(&path, rotated_path(&path, 1))
+        .with_context(|| "game_logs::rotate_log() Failed to rotate log! | Err: ")
+}
+
+/// Returns the last lines of a game's log, or all of it if lines is None.
+pub fn read_log(id: &str, lines: Option<usize>) -> Result<String> {
+    let path: PathBuf = get_game_log_path(id);
+    if !path.exists() {
+        return Ok(String::new());
+    }
+
+    let bytes: Vec<u8> = fs::read(&path).with_context(|| {
+        format!(
+            "game_logs::read_log() Failed to read: {file} | Err: ",
+            file = path.display()
+        )
+    })?;
+    let content: String = String::from_utf8_lossy(&bytes).into_owned();
+
+    match lines {
+        Some(lines) => Ok(last_lines(&content, lines)),
+        None => Ok(content),
+    }
+}
+
+/// Returns everything written to a game's log since offset, used to follow a log.
+/// Starts over from the beginning if the log has been rotated since.
+pub fn read_log_from(id: &str, offset: u64) -> Result<LogChunk> {
+    let path: PathBuf = get_game_log_path(id);
+    let mut file: File = match File::open(&path) {
+        Ok(file) => file,
+        Err(_) => {
+            return Ok(LogChunk {
+                content: String::new(),
+                offset: 0,
+            })
+        }
+    };
+
+    let len: u64 = file
+        .metadata()
+        .with_context(|| "game_logs::read_log_from() Failed to read log metadata! | Err: ")?
+        .len();
+    let offset: u64 = if offset > len { 0 } else { offset };
+
+    file.seek(SeekFrom::Start(offset))
+        .with_context(|| "game_logs::read_log_from() Failed to seek in log! | Err: ")?;
+    let mut bytes: Vec<u8> = Vec::new();
+    file.read_to_end(&mut bytes)
+        .with_context(|| "game_logs::read_log_from() Failed to read log! | Err: ")?;
+
+    Ok(LogChunk {
+        content: String::from_utf8_lossy(&bytes).into_owned(),
+        offset: offset + bytes.len() as u64,
+    })
+}
+
+/// Follows a game's log in the Monarch terminal until the user closes it.
+pub async fn open_log_in_terminal(handle: &AppHandle, id: &str) -> Result<()> {
+    let path: PathBuf = get_game_log_path(id);
+    let path: String = path.display().to_string();
+    let lines: String = TERMINAL_LINES.to_string();
+
+    let cmd: CommandBuilder = if cfg!(windows) {
+        let mut cmd = CommandBuilder::new("powershell.exe");
+        cmd.args(["Get-Content", "-Path", &path, "-Tail", &lines, "-Wait"]);
+        cmd
+    } else {
+        let mut cmd = CommandBuilder::new("tail");
+        cmd.args(["-n", &lines, "-F", &path]);
+        cmd
+    };
+
+    run_command_in_terminal(handle, cmd)
+        .await
+        .with_context(|| "game_logs::open_log_in_terminal() -> ")?;
+    Ok(())
+}
+
+/// Returns the last lines of text.
+pub fn last_lines(content: &str, lines: usize) -> String {
+    let all: Vec<&str> = content.lines().collect();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+fn rotated_path(path: &Path, i: usize) -> PathBuf {
+    path.with_extension(format!("{i}.log"))
+}
@@ -0,0 +1,386 @@
+/*
+* Process supervisor for games launched by Monarch.
+*
+* Games launched directly (binary or Proton) get MONARCH_GAME_ID set in their environment,
+* which is inherited by every child process, including wineserver after it has been
+* reparented. Games launched through Steam are found through the reaper process Steam
+* wraps around them.
+*/
+
+use anyhow::{bail, Result};
+use once_cell::sync::{Lazy, OnceCell};
+use portable_pty::ExitStatus;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::thread;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, PidExt, ProcessExt, Signal, System, SystemExt};
+use tauri::{AppHandle, Emitter};
+use tracing::{error, info, warn};
+
+use super::crash_report::{self, crash_reason, CrashReport, ExitDetails};
+use super::game_logs;
+use super::launch_hooks::{self, HookContext, LaunchHooks};
+use super::launch_spec::LaunchSpec;
+use super::monarchgame::{LaunchProfile, MonarchGame};
+use crate::monarch_library::play_history::{self, unix_now};
+use crate::monarch_utils::monarch_settings::get_settings_state;
+
+/// Environment variable used to recognize processes belonging to a game.
+pub const GAME_ID_ENV: &str = "MONARCH_GAME_ID";
+
+/// How long a launched game is allowed to take before any of its processes show up.
+const LAUNCH_GRACE: Duration = Duration::from_secs(60);
+
+/// How long stop_game() waits for a game to exit before killing it.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the monitor looks for running games.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+static APP_HANDLE: OnceCell<AppHandle> = OnceCell::new();
+
+static RUNNING_GAMES: Lazy<Mutex<HashMap<String, TrackedGame>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A game Monarch has launched and is still running, as sent to the frontend.
+#[derive(Serialize, Clone, Debug)]
+pub struct RunningGame {
+    pub id: String,
+    pub name: String,
+    pub platform: String,
+    pub platform_id: String,
+    pub pids: Vec<u32>,
+    pub started_at: u64,
+    pub profile: String, // Launch profile used, empty if none
+}
+
+struct TrackedGame {
+    game: RunningGame,
+    launched: MonarchGame,      // Game as launched, with its launch profile applied
+    spec: Option<LaunchSpec>,   // Command the game was launched with, if launched directly
+    exit: Option<ExitStatus>,   // Exit status of the process Monarch started, if known
+    stopping: bool,             // Stopped by the user, so exiting isn't a crash
+    seen: bool,
+    launcher_exited: bool,
+    grace_until: Instant,
+}
+
+/// Starts the monitor loop on a seperate thread.
+pub fn start(handle: AppHandle) {
+    let _ = APP_HANDLE.set(handle.clone());
+
+    thread::spawn(move || {
+        let mut sys: System = System::new();
+
+        loop {
+            sleep(POLL_INTERVAL);
+
+            if is_empty() {
+                continue;
+            }
+
+            sys.refresh_processes();
+            if refresh_running_games(&sys) {
+                if let Err(e) = handle.emit("running-games", get_running_games()) {
+                    error!("game_monitor::start() Failed to emit running-games event! | Err: {e}");
+                }
+            }
+        }
+    });
+}
+
+/// Starts tracking a game that is about to be launched.
+/// Fails if the game is already running, to stop it from being launched twice.
+pub fn register(game: &MonarchGame, profile: Option<&LaunchProfile>) -> Result<()> {
+    let mut games = lock_games();
+
+    if games.contains_key(&game.id) {
+        bail!(
+            "game_monitor::register() | Err: {} is already running!",
+            game.name
+        )
+    }
+
+    games.insert(
+        game.id.clone(),
+        TrackedGame {
+            game: RunningGame {
+                id: game.id.clone(),
+                name: game.name.clone(),
+                platform: game.platform.clone(),
+                platform_id: game.platform_id.clone(),
+                pids: Vec::new(),
+                started_at: unix_now(),
+                profile: profile.map(|p| p.name.clone()).unwrap_or_default(),
+            },
+            launched: game.clone(),
+            spec: None,
+            exit: None,
+            stopping: false,
+            seen: false,
+            launcher_exited: false,
+            grace_until: Instant::now() + LAUNCH_GRACE,
+        },
+    );
+    Ok(())
+}
+
+/// Stops tracking a game, for example when launching it failed.
+pub fn unregister(id: &str) {
+    lock_games().remove(id);
+}
+
+/// Remembers the command a game was launched with, included in crash reports.
+pub fn set_launch_spec(id: &str, spec: &LaunchSpec) {
+    if let Some(tracked) = lock_games().get_mut(id) {
+        tracked.spec = Some(spec.clone());
+    }
+}
+
+/// Tells the monitor that the process Monarch started has exited, with its exit status if known.
+/// The game is dropped on the next poll unless any of its children are still alive.
+pub fn launcher_exited(id: &str, exit: Option<ExitStatus>) {
+    if let Some(tracked) = lock_games().get_mut(id) {
+        tracked.launcher_exited = true;
+        tracked.exit = exit;
+        tracked.grace_until = Instant::now();
+    }
+}
+
+/// Returns all games currently running.
+pub fn get_running_games() -> Vec<RunningGame> {
+    let mut games: Vec<RunningGame> = lock_games()
+        .values()
+        .map(|tracked| tracked.game.clone())
+        .collect();
+    games.sort_by_key(|game| game.started_at);
+    games
+}
+
+/// Asks a game to exit and kills whatever is left of it after a timeout.
+pub async fn stop_game(id: &str) -> Result<()> {
+    let game: RunningGame = match lock_games().get_mut(id) {
+        Some(tracked) => {
+            tracked.stopping = true;
+            tracked.game.clone()
+        }
+        None => bail!("game_monitor::stop_game() | Err: Game with id: {id} is not running!"),
+    };
+
+    info!("Stopping game: {}", game.name);
+    let mut sys: System = System::new();
+    sys.refresh_processes();
+
+    for pid in find_game_processes(&sys, &game) {
+        if let Some(process) = sys.process(pid) {
+            if process.kill_with(Signal::Term).is_none() {
+                // SIGTERM is not supported on all platforms
+                process.kill();
+            }
+        }
+    }
+
+    let deadline: Instant = Instant::now() + STOP_TIMEOUT;
+    while Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        sys.refresh_processes();
+
+        if find_game_processes(&sys, &game).is_empty() {
+            finish(id);
+            return Ok(());
+        }
+    }
+
+    warn!("{} did not exit in time! Killing remaining processes...", game.name);
+    for pid in find_game_processes(&sys, &game) {
+        if let Some(process) = sys.process(pid) {
+            if process.kill_with(Signal::Kill).is_none() {
+                process.kill();
+            }
+        }
+    }
+
+    finish(id);
+    Ok(())
+}
+
+/// Stops tracking a game that has exited and records the session.
+fn finish(id: &str) {
+    let tracked: Option<TrackedGame> = lock_games().remove(id);
+    if let Some(tracked) = tracked {
+        game_exited(tracked);
+    }
+}
+
+/// Everything that should happen once a game has exited.
+fn game_exited(tracked: TrackedGame) {
+    info!("Game exited: {}", tracked.game.name);
+
+    // Steam launches that never showed up were probably never started
+    let mut session_seconds: Option<u64> = None;
+    if tracked.seen || tracked.launcher_exited {
+        let seconds: u64 = unix_now().saturating_sub(tracked.game.started_at);
+        play_history::record_session(&tracked.game.id, seconds);
+        session_seconds = Some(seconds);
+    }
+
+    // Reports and hooks can take a while, so they're kept off the monitor thread
+    thread::spawn(move || {
+        if let Some(seconds) = session_seconds.filter(|_| !tracked.stopping) {
+            if let Some(reason) = crash_reason(tracked.exit.as_ref(), seconds) {
+                report_crash(&tracked, reason, seconds);
+            }
+        }
+
+        let context: HookContext = HookContext {
+            exit_code: tracked.exit.as_ref().map(|status| status.exit_code() as i64),
+            session_seconds,
+            ..HookContext::from(&tracked.launched)
+        };
+        let global_hooks: LaunchHooks = get_settings_state().launch.hooks;
+        launch_hooks::run_post_exit(&global_hooks, &tracked.launched.hooks, &context);
+    });
+}
+
+/// Writes a crash report and lets the frontend know about it.
+/// Games launched in the background also get their log opened in the terminal.
+fn report_crash(tracked: &TrackedGame, reason: String, session_seconds: u64) {
+    warn!("{} crashed: {reason}", tracked.game.name);
+
+    let details: ExitDetails = ExitDetails {
+        game: &tracked.launched,
+        spec: tracked.spec.as_ref(),
+        status: tracked.exit.as_ref(),
+        session_seconds,
+    };
+    let report: CrashReport = match crash_report::create_report(details, reason) {
+        Ok(report) => report,
+        Err(e) => {
+            error!("game_monitor::report_crash() -> {e}");
+            return;
+        }
+    };
+
+    let handle: AppHandle = match APP_HANDLE.get() {
+        Some(handle) => handle.clone(),
+        None => return,
+    };
+    if let Err(e) = handle.emit("game-crashed", &report) {
+        error!("game_monitor::report_crash() Failed to emit game-crashed event! | Err: {e}");
+    }
+
+    let detached: bool = tracked.spec.is_some() && !tracked.launched.show_terminal;
+    if detached {
+        let id: String = tracked.game.id.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = game_logs::open_log_in_terminal(&handle, &id).await {
+                error!("game_monitor::report_crash() -> {e}");
+            }
+        });
+    }
+}
+
+/// Updates process info of tracked games and drops games that have exited.
+/// Returns whether anything changed.
+fn refresh_running_games(sys: &System) -> bool {
+    let mut games = lock_games();
+    let mut changed: bool = false;
+    let mut exited: Vec<String> = Vec::new();
+    let mut finished: Vec<TrackedGame> = Vec::new();
+
+    for (id, tracked) in games.iter_mut() {
+        let mut pids: Vec<u32> = find_game_processes(sys, &tracked.game)
+            .into_iter()
+            .map(|pid| pid.as_u32())
+            .collect();
+        pids.sort_unstable();
+
+        if pids.is_empty() {
+            if tracked.seen || Instant::now() >= tracked.grace_until {
+                exited.push(id.clone());
+            }
+            continue;
+        }
+
+        tracked.seen = true;
+        if pids != tracked.game.pids {
+            tracked.game.pids = pids;
+            changed = true;
+        }
+    }
+
+    for id in exited {
+        if let Some(tracked) = games.remove(&id) {
+            finished.push(tracked);
+            changed = true;
+        }
+    }
+    drop(games);
+
+    for tracked in finished {
+        game_exited(tracked);
+    }
+
+    changed
+}
+
+/// Returns every process belonging to a game, including all of its children.
+fn find_game_processes(sys: &System, game: &RunningGame) -> Vec<Pid> {
+    let env_tag: String = format!("{GAME_ID_ENV}={}", game.id);
+    let app_id_arg: String = format!("AppId={}", game.platform_id);
+    let is_steam: bool = matches!(game.platform.as_str(), "steam" | "steamcmd");
+
+    let roots: HashSet<Pid> = sys
+        .processes()
+        .iter()
+        .filter(|(_, process)| {
+            process.environ().contains(&env_tag)
+                || (is_steam && is_steam_reaper(process.cmd(), &app_id_arg))
+        })
+        .map(|(pid, _)| *pid)
+        .collect();
+
+    if roots.is_empty() {
+        return Vec::new();
+    }
+
+    sys.processes()
+        .keys()
+        .filter(|pid| has_ancestor_in(sys, **pid, &roots))
+        .copied()
+        .collect()
+}
+
+/// Steam launches games as: reaper SteamLaunch AppId=<id> -- <command>
+fn is_steam_reaper(cmd: &[String], app_id_arg: &str) -> bool {
+    cmd.iter().any(|arg| arg == "SteamLaunch") && cmd.iter().any(|arg| arg == app_id_arg)
+}
+
+/// Returns whether a process, or any of its parents, is in roots.
+fn has_ancestor_in(sys: &System, pid: Pid, roots: &HashSet<Pid>) -> bool {
+    let mut current: Option<Pid> = Some(pid);
+
+    // Depth is limited in case the process table changes under us and forms a loop.
+    for _ in 0..64 {
+        match current {
+            Some(p) if roots.contains(&p) => return true,
+            Some(p) => current = sys.process(p).and_then(|process| process.parent()),
+            None => return false,
+        }
+    }
+    false
+}
+
+fn is_empty() -> bool {
+    lock_games().is_empty()
+}
+
+/// Locks the tracked games, recovering the map if another thread panicked while holding it.
+fn lock_games() -> std::sync::MutexGuard<'static, HashMap<String, TrackedGame>> {
+    RUNNING_GAMES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,188 @@
+/*
+* Commands run before a game starts or after it exits.
+*
+* Hooks can be set globally in settings, per game and per launch profile, where a profile's
+* hooks replace the game's. Global pre-launch hooks run before the game's, and global post-exit
+* hooks after the game's. Hooks get information about the game through these variables:
+*
+*   MONARCH_GAME_ID, MONARCH_GAME_NAME, MONARCH_INSTALL_PATH,
+*   MONARCH_EXIT_CODE and MONARCH_SESSION_SECONDS (post-exit only, empty if unknown)
+*
+* Their output is appended to the game's log.
+*/
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use super::game_logs;
+use super::game_monitor::GAME_ID_ENV;
+use super::monarchgame::MonarchGame;
+
+/// Timeout used when a hook doesn't set one.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often a running hook is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Hooks set globally or for a single game.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct LaunchHooks {
+    pub pre_launch: String, // Command to run before the game starts
+    pub post_exit: String,  // Command to run after the game exits
+    pub timeout: u64,       // Seconds a hook may run before it's killed, 0 for the default
+    pub abort_on_failure: bool, // Whether a failing pre-launch hook stops the game from launching
+}
+
+impl Default for LaunchHooks {
+    fn default() -> Self {
+        Self {
+            pre_launch: String::new(),
+            post_exit: String::new(),
+            timeout: 0,
+            abort_on_failure: true,
+        }
+    }
+}
+
+impl LaunchHooks {
+    fn timeout(&self) -> Duration {
+        match self.timeout {
+            0 => DEFAULT_TIMEOUT,
+            seconds => Duration::from_secs(seconds),
+        }
+    }
+}
+
+/// Information about a game passed to its hooks.
+#[derive(Clone, Debug, Default)]
+pub struct HookContext {
+    pub game_id: String,
+    pub game_name: String,
+    pub install_path: String,
+    pub exit_code: Option<i64>,
+    pub session_seconds: Option<u64>,
+}
+
+impl From<&MonarchGame> for HookContext {
+    fn from(game: &MonarchGame) -> Self {
+        let install_path: String = if game.install_dir.is_empty() {
+            Path::new(&game.executable_path)
+                .parent()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default()
+        } else {
+            game.install_dir.clone()
+        };
+
+        Self {
+            game_id: game.id.clone(),
+            game_name: game.name.clone(),
+            install_path,
+            ..Default::default()
+        }
+    }
+}
+
+/// Runs the global and then the game's pre-launch hook.
+/// Fails if a hook that should abort the launch fails.
+pub fn run_pre_launch(global: &LaunchHooks, game: &LaunchHooks, context: &HookContext) -> Result<()> {
+    for hooks in [global, game] {
+        if hooks.pre_launch.is_empty() {
+            continue;
+        }
+
+        if let Err(e) = run_hook(&hooks.pre_launch, hooks.timeout(), context) {
+            if hooks.abort_on_failure {
+                return Err(e.context("launch_hooks::run_pre_launch() Aborting launch! -> "));
+            }
+            warn!("launch_hooks::run_pre_launch() Pre-launch hook failed, launching anyway. -> {e}");
+        }
+    }
+    Ok(())
+}
+
+/// Runs the game's and then the global post-exit hook. Failures are only logged,
+/// as there is nothing left to abort.
+pub fn run_post_exit(global: &LaunchHooks, game: &LaunchHooks, context: &HookContext) {
+    for hooks in [game, global] {
+        if hooks.post_exit.is_empty() {
+            continue;
+        }
+
+        if let Err(e) = run_hook(&hooks.post_exit, hooks.timeout(), context) {
+            warn!("launch_hooks::run_post_exit() -> {e}");
+        }
+    }
+}
+
+/// Runs a hook through the system shell and waits for it to finish, killing it after timeout.
+/// Fails if the hook can't be started, times out or exits unsuccessfully.
+pub fn run_hook(command: &str, timeout: Duration, context: &HookContext) -> Result<()> {
+    info!("Running hook for {}: {command}", context.game_name);
+
+    let log: File = game_logs::open_log(&context.game_id, &format!("Running hook: {command}"))
+        .with_context(|| "launch_hooks::run_hook() -> ")?;
+    let log_err: File = log
+        .try_clone()
+        .with_context(|| "launch_hooks::run_hook() Failed to clone log file handle! | Err: ")?;
+
+    let mut cmd: Command = if cfg!(windows) {
+        let mut cmd = Command::new("powershell.exe");
+        cmd.args(["-Command", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+
+    let optional = |value: Option<String>| value.unwrap_or_default();
+    let mut child: Child = cmd
+        .env(GAME_ID_ENV, &context.game_id)
+        .env("MONARCH_GAME_NAME", &context.game_name)
+        .env("MONARCH_INSTALL_PATH", &context.install_path)
+        .env("MONARCH_EXIT_CODE", optional(context.exit_code.map(|c| c.to_string())))
+        .env(
+            "MONARCH_SESSION_SECONDS",
+            optional(context.session_seconds.map(|s| s.to_string())),
+        )
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(log))
+        .stderr(Stdio::from(log_err))
+        .spawn()
+        .with_context(|| format!("launch_hooks::run_hook() Failed to run hook: {command} | Err: "))?;
+
+    let deadline: Instant = Instant::now() + timeout;
+    let status: ExitStatus = loop {
+        if let Some(status) = child
+            .try_wait()
+            .with_context(|| format!("launch_hooks::run_hook() Failed to wait for hook: {command} | Err: "))?
+        {
+            break status;
+        }
+
+        if Instant::now() >= deadline {
+            if let Err(e) = child.kill() {
+                warn!("launch_hooks::run_hook() Failed to kill hook: {command} | Err: {e}");
+            }
+            let _ = child.wait();
+            bail!(
+                "launch_hooks::run_hook() | Err: Hook: {command} timed out after {} seconds!",
+                timeout.as_secs()
+            )
+        }
+        sleep(POLL_INTERVAL);
+    };
+
+    if !status.success() {
+        bail!("launch_hooks::run_hook() | Err: Hook: {command} exited with: {status}")
+    }
+    Ok(())
+}
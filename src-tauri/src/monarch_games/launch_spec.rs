@@ -0,0 +1,263 @@
+/*
+* Structured command lines for launching games.
+*
+* Launch arguments follow Steam's launch option rules. If they contain %command% it is replaced
+* by the game's own command, anything before it is a wrapper (such as gamemoderun) and leading
+* VAR=value words are set as environment variables:
+*
+*   DXVK_HUD=1 gamemoderun %command% -novid
+*
+* Without %command% the arguments are simply passed to the game. Arguments are split with shell
+* quoting rules, but never run through a shell, so nothing in them is expanded or executed.
+*/
+
+use anyhow::{bail, Result};
+use portable_pty::CommandBuilder;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Placeholder for the game's own command in launch arguments.
+pub const COMMAND_PLACEHOLDER: &str = "%command%";
+
+/// Everything needed to start a game process.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct LaunchSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub cwd: Option<PathBuf>,
+}
+
+impl LaunchSpec {
+    /// Builds a launch spec from the game's command, such as its executable or
+    /// "proton run game.exe", and the launch arguments set by the user.
+    pub fn new(command: Vec<String>, launch_args: &str) -> Result<Self> {
+        if command.is_empty() || command[0].is_empty() {
+            bail!("launch_spec::LaunchSpec::new() | Err: No command to launch!")
+        }
+
+        let words: Vec<String> = split_shell_words(launch_args)?;
+        let mut spec: LaunchSpec = LaunchSpec::default();
+
+        let argv: Vec<String> = match words.iter().position(|w| w == COMMAND_PLACEHOLDER) {
+            Some(index) => {
+                let mut before = words[..index].iter().peekable();
+
+                // Leading VAR=value words are environment variables, the rest are wrappers
+                while let Some((key, value)) = before.peek().and_then(|w| parse_assignment(w)) {
+                    spec.env.insert(key.to_string(), value.to_string());
+                    before.next();
+                }
+
+                before
+                    .cloned()
+                    .chain(command)
+                    .chain(words[index + 1..].iter().cloned())
+                    .collect()
+            }
+            None => command.into_iter().chain(words).collect(),
+        };
+
+        let mut argv = argv.into_iter();
+        spec.program = argv.next().unwrap_or_default();
+        spec.args = argv.collect();
+        Ok(spec)
+    }
+
+    /// Sets an environment variable, replacing any earlier value.
+    pub fn set_env(&mut self, key: &str, value: &str) {
+        self.env.insert(key.to_string(), value.to_string());
+    }
+
+    /// Sets environment variables only if they haven't been set already,
+    /// used for defaults that launch arguments should be able to override.
+    pub fn default_env<'a>(&mut self, vars: impl IntoIterator<Item = (&'a str, &'a str)>) {
+        for (key, value) in vars {
+            self.env
+                .entry(key.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+    }
+}
+
+impl From<&LaunchSpec> for CommandBuilder {
+    fn from(spec: &LaunchSpec) -> Self {
+        let mut cmd: CommandBuilder = CommandBuilder::new(&spec.program);
+        cmd.args(&spec.args);
+
+        for (key, value) in spec.env.iter() {
+            cmd.env(key, value);
+        }
+        if let Some(cwd) = &spec.cwd {
+            cmd.cwd(cwd);
+        }
+        cmd
+    }
+}
+
+impl From<&LaunchSpec> for Command {
+    fn from(spec: &LaunchSpec) -> Self {
+        let mut cmd: Command = Command::new(&spec.program);
+        cmd.args(&spec.args).envs(&spec.env);
+
+        if let Some(cwd) = &spec.cwd {
+            cmd.current_dir(cwd);
+        }
+        cmd
+    }
+}
+
+impl fmt::Display for LaunchSpec {
+    /// Shell-quoted command line, only meant for logs and previews.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let words: Vec<String> = std::iter::once(&self.program)
+            .chain(self.args.iter())
+            .map(|word| quote_shell_word(word))
+            .collect();
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+/// Splits text into words the way a POSIX shell would, handling quotes and backslashes.
+/// Variables, globs and other shell features are left as they are.
+pub fn split_shell_words(input: &str) -> Result<Vec<String>> {
+    let mut words: Vec<String> = Vec::new();
+    let mut word: String = String::new();
+    let mut in_word: bool = false;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => bail!("launch_spec::split_shell_words() | Err: Unclosed ' in: {input}"),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // Inside double quotes a backslash only escapes these characters
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => bail!("launch_spec::split_shell_words() | Err: Unclosed \" in: {input}"),
+                        },
+                        Some(c) => word.push(c),
+                        None => bail!("launch_spec::split_shell_words() | Err: Unclosed \" in: {input}"),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(c) => {
+                    in_word = true;
+                    word.push(c);
+                }
+                None => bail!("launch_spec::split_shell_words() | Err: Trailing \\ in: {input}"),
+            },
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Quotes a word so that split_shell_words() would return it unchanged.
+pub fn quote_shell_word(word: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+%@".contains(c);
+
+    if !word.is_empty() && word.chars().all(is_plain) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', r#"'\''"#))
+}
+
+/// Returns key and value of words such as VAR=value.
+fn parse_assignment(word: &str) -> Option<(&str, &str)> {
+    let (key, value) = word.split_once('=')?;
+    let mut chars = key.chars();
+
+    let valid_start: bool = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    let valid_rest: bool = chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    (valid_start && valid_rest).then_some((key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(input: &str) -> Vec<String> {
+        split_shell_words(input).unwrap()
+    }
+
+    #[test]
+    fn split_shell_words_follows_shell_quoting() {
+        assert_eq!(words("  -novid   -high "), ["-novid", "-high"]);
+        assert_eq!(words(r#"-name "John Doe" 'it''s'"#), ["-name", "John Doe", "its"]);
+        assert_eq!(words(r#""a \"b\" \n" c\ d"#), [r#"a "b" \n"#, "c d"]);
+        assert_eq!(words(r#"'$HOME' "" x"#), ["$HOME", "", "x"]);
+        assert!(words("").is_empty());
+
+        for unclosed in ["'open", "\"open", "trailing\\"] {
+            assert!(split_shell_words(unclosed).is_err(), "Split invalid input: {unclosed}");
+        }
+    }
+
+    #[test]
+    fn quoted_words_split_back_unchanged() {
+        let originals: [&str; 5] = ["plain", "two words", "it's", "", "$HOME\\\"x\""];
+        let line: String = originals
+            .iter()
+            .map(|word| quote_shell_word(word))
+            .collect::<Vec<String>>()
+            .join(" ");
+        assert_eq!(words(&line), originals);
+    }
+
+    #[test]
+    fn command_placeholder_splits_env_wrappers_and_args() {
+        let command: Vec<String> = vec![String::from("/games/hl2"), String::from("-game")];
+        let spec: LaunchSpec =
+            LaunchSpec::new(command.clone(), "DXVK_HUD=1 gamemoderun %command% -novid").unwrap();
+
+        assert_eq!(spec.program, "gamemoderun");
+        assert_eq!(spec.args, ["/games/hl2", "-game", "-novid"]);
+        assert_eq!(spec.env.get("DXVK_HUD").map(String::as_str), Some("1"));
+
+        let spec: LaunchSpec = LaunchSpec::new(command, "-novid FOO=bar").unwrap();
+        assert_eq!(spec.program, "/games/hl2");
+        assert_eq!(spec.args, ["-game", "-novid", "FOO=bar"]);
+        assert!(spec.env.is_empty());
+
+        assert!(LaunchSpec::new(Vec::new(), "-novid").is_err());
+    }
+}
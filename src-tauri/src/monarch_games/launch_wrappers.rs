@@ -0,0 +1,186 @@
+/*
+* Programs that wrap around a game's command, such as gamescope and MangoHud.
+*
+* Wrappers can be set per game and as defaults in settings. Options left unset on a game fall
+* back to the defaults. Wrappers are composed around the game in this order:
+*
+*   gamemoderun gamescope <options> -- obs-gamecapture mangohud <game command>
+*
+* When gamescope is used, MangoHud is run through its --mangoapp option instead.
+*/
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::launch_spec::LaunchSpec;
+
+/// Error returned when wrappers are enabled but not installed, meant to be shown to the user.
+#[derive(Debug)]
+pub struct MissingWrappers(pub Vec<String>);
+
+impl fmt::Display for MissingWrappers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Could not find: {}. Make sure they are installed!", self.0.join(", "))
+    }
+}
+
+impl std::error::Error for MissingWrappers {}
+
+/// Wrapper options, None means the default from settings is used.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct LaunchWrappers {
+    pub gamescope: Option<GamescopeOptions>,
+    pub mangohud: Option<MangoHudOptions>,
+    pub gamemode: Option<bool>,
+    pub obs_gamecapture: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct GamescopeOptions {
+    pub enabled: bool,
+    pub width: u32,   // Output resolution, 0 to let gamescope decide
+    pub height: u32,
+    pub refresh: u32, // Refresh rate in Hz, 0 to let gamescope decide
+    pub upscaler: String, // fsr, nis, linear, nearest, integer, fit, fill or stretch
+    pub hdr: bool,
+    pub fullscreen: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct MangoHudOptions {
+    pub enabled: bool,
+    pub config: String, // Either a MANGOHUD_CONFIG string or a path to a config file
+}
+
+impl LaunchWrappers {
+    /// Fills options not set here with the ones from defaults.
+    pub fn or(&self, defaults: &LaunchWrappers) -> LaunchWrappers {
+        LaunchWrappers {
+            gamescope: self.gamescope.clone().or_else(|| defaults.gamescope.clone()),
+            mangohud: self.mangohud.clone().or_else(|| defaults.mangohud.clone()),
+            gamemode: self.gamemode.or(defaults.gamemode),
+            obs_gamecapture: self.obs_gamecapture.or(defaults.obs_gamecapture),
+        }
+    }
+
+    /// Wraps a launch spec in every enabled wrapper.
+    /// Fails with MissingWrappers if any of them can't be found in PATH.
+    pub fn apply(&self, spec: &mut LaunchSpec) -> Result<()> {
+        let gamescope: Option<&GamescopeOptions> =
+            self.gamescope.as_ref().filter(|options| options.enabled);
+        let mangohud: Option<&MangoHudOptions> =
+            self.mangohud.as_ref().filter(|options| options.enabled);
+
+        let mut wrapper: Vec<String> = Vec::new();
+
+        if self.gamemode == Some(true) {
+            wrapper.push(String::from("gamemoderun"));
+        }
+        if let Some(options) = gamescope {
+            wrapper.extend(gamescope_args(options, mangohud.is_some()));
+        }
+        if self.obs_gamecapture == Some(true) {
+            wrapper.push(String::from("obs-gamecapture"));
+        }
+        if let Some(options) = mangohud {
+            if gamescope.is_none() {
+                wrapper.push(String::from("mangohud"));
+            }
+            set_mangohud_config(options, spec);
+        }
+
+        if wrapper.is_empty() {
+            return Ok(());
+        }
+
+        check_installed(&wrapper)?;
+
+        let program: String = std::mem::replace(&mut spec.program, wrapper.remove(0));
+        wrapper.push(program);
+        wrapper.append(&mut spec.args);
+        spec.args = wrapper;
+        Ok(())
+    }
+}
+
+fn gamescope_args(options: &GamescopeOptions, mangoapp: bool) -> Vec<String> {
+    let mut args: Vec<String> = vec![String::from("gamescope")];
+
+    if options.width > 0 && options.height > 0 {
+        args.extend([
+            String::from("-W"),
+            options.width.to_string(),
+            String::from("-H"),
+            options.height.to_string(),
+        ]);
+    }
+    if options.refresh > 0 {
+        args.extend([String::from("-r"), options.refresh.to_string()]);
+    }
+
+    match options.upscaler.as_str() {
+        "" => {}
+        filter @ ("fsr" | "nis" | "linear" | "nearest" | "pixel") => {
+            args.extend([String::from("-F"), filter.to_string()])
+        }
+        scaler => args.extend([String::from("-S"), scaler.to_string()]),
+    }
+
+    if options.hdr {
+        args.push(String::from("--hdr-enabled"));
+    }
+    if options.fullscreen {
+        args.push(String::from("-f"));
+    }
+    if mangoapp {
+        args.push(String::from("--mangoapp"));
+    }
+
+    args.push(String::from("--"));
+    args
+}
+
+/// MangoHud reads its config from MANGOHUD_CONFIG, or a file given in MANGOHUD_CONFIGFILE.
+fn set_mangohud_config(options: &MangoHudOptions, spec: &mut LaunchSpec) {
+    let config: &str = options.config.trim();
+    if config.is_empty() {
+        return;
+    }
+
+    if Path::new(config).is_file() {
+        spec.default_env([("MANGOHUD_CONFIGFILE", config)]);
+    } else {
+        spec.default_env([("MANGOHUD_CONFIG", config)]);
+    }
+}
+
+/// Checks that every program in a wrapper command can be found.
+fn check_installed(wrapper: &[String]) -> Result<()> {
+    let programs = ["gamemoderun", "gamescope", "obs-gamecapture", "mangohud"];
+
+    let missing: Vec<String> = wrapper
+        .iter()
+        .filter(|word| programs.contains(&word.as_str()))
+        .filter(|program| find_in_path(program).is_none())
+        .cloned()
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(MissingWrappers(missing).into());
+    }
+    Ok(())
+}
+
+/// Returns the full path of a program in PATH, like `which`.
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
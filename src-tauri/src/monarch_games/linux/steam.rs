@@ -6,6 +6,7 @@ use crate::monarch_utils::{
     monarch_vdf,
 };
 use anyhow::{Context, Result};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::process::Command;
 use tauri::AppHandle;
@@ -42,18 +43,26 @@ exit;"#,
     Ok(())
 }
 
+/// Returns path to the SteamCMD script installed by Monarch
+pub fn steamcmd_executable() -> PathBuf {
+    get_steamcmd_dir().join("steamcmd.sh")
+}
+
 /// Runs specified command via SteamCMD
 /// Is currently async to work with Windows version
 /// TODO: Come back and add a way of showing the output of SteamCMD
-pub async fn steamcmd_command(handle: &AppHandle, args: Vec<&str>) -> Result<()> {
-    let mut path: PathBuf = get_steamcmd_dir();
-    path.push("steamcmd.sh");
+pub async fn steamcmd_command(
+    handle: &AppHandle,
+    args: Vec<&str>,
+    env: &BTreeMap<String, String>,
+) -> Result<()> {
+    let path: PathBuf = steamcmd_executable();
     let args_string: String = args.iter().map(|arg| format!("{arg} ")).collect::<String>();
 
     run_in_terminal(
         handle,
         &format!("{} {}; sleep 3;", path.display(), args_string),
-        None,
+        Some(env.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect()),
     )
     .await
     .with_context(|| "linux::steam::steamcmd_command() -> ")?;
@@ -134,8 +143,8 @@ pub fn get_default_libraryfolders_location() -> Result<PathBuf> {
 }
 
 /// Runs specified command via Steam
-pub fn run_command(args: &str) -> Result<()> {
-    Command::new("steam").arg(args).spawn().with_context(|| {
+pub fn run_command(args: &str, env: &BTreeMap<String, String>) -> Result<()> {
+    Command::new("steam").arg(args).envs(env).spawn().with_context(|| {
         format!("linux::steam::run_command() Failed to run Steam command {args} | Err")
     })?;
 
@@ -2,6 +2,7 @@ use crate::monarch_games::monarchgame::MonarchGame;
 use anyhow::{bail, Result};
 use tauri::AppHandle;
 use tracing::error;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 /*
@@ -14,11 +15,20 @@ pub async fn install_steamcmd(handle: &AppHandle) -> Result<()> {
     bail!("monarch_games::macos::install_steamcmd() MacOS not currently supported!");
 }
 
-pub async fn steamcmd_command(handle: &AppHandle, args: Vec<&str>) -> Result<()> {
+pub async fn steamcmd_command(
+    handle: &AppHandle,
+    args: Vec<&str>,
+    env: &BTreeMap<String, String>,
+) -> Result<()> {
     error!("monarch_games::macos::steamcmd_command() MacOS not currently supported!");
     bail!("monarch_games::macos::steamcmd_command() MacOS not currently supported!");
 }
 
+pub fn steamcmd_executable() -> PathBuf {
+    error!("monarch_games::macos::steamcmd_executable() MacOS not currently supported!");
+    PathBuf::new()
+}
+
 pub fn steam_is_installed() -> bool {
     error!("monarch_games::macos::steam_is_installed() MacOS not currently supported!");
     false
@@ -29,7 +39,7 @@ pub async fn get_library() -> Vec<MonarchGame> {
     vec![]
 }
 
-pub fn run_command(args: &str) -> Result<()> {
+pub fn run_command(args: &str, env: &BTreeMap<String, String>) -> Result<()> {
     error!("monarch_games::macos::run_command() MacOS not currently supported!");
     bail!("monarch_games::macos::run_command() MacOS not currently supported!");
 }
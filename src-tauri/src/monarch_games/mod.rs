@@ -1,7 +1,17 @@
 pub mod commands;
+pub mod crash_report;
+pub mod download_queue;
+pub mod game_logs;
+pub mod game_monitor;
+pub mod launch_hooks;
+pub mod launch_spec;
+pub mod launch_wrappers;
 pub mod monarch_client;
 pub mod monarchgame;
+pub mod steam_appinfo;
 pub mod steam_client;
+pub mod steamcmd;
+pub mod steamcmd_session;
 
 #[cfg(target_os = "windows")]
 pub mod windows;
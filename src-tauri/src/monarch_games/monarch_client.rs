@@ -1,15 +1,30 @@
+use super::crash_report;
+use super::download_queue::{self, DownloadKind, InstallOptions, QueuedDownload};
+use super::game_logs;
+use super::game_monitor::{self, GAME_ID_ENV};
+use super::launch_hooks::{self, HookContext, LaunchHooks};
+use super::launch_spec::LaunchSpec;
+use super::launch_wrappers::LaunchWrappers;
+use super::monarchgame::LaunchProfile;
+use super::steam_appinfo::{self, AppInfo};
+use super::steamcmd;
 use super::{monarchgame::MonarchGame, steam_client};
 use crate::monarch_games::monarchgame::MonarchWebGame;
+use crate::monarch_library::collections::remove_game_from_collections;
 use crate::monarch_library::games_library::write_monarch_games;
-use crate::monarch_utils::monarch_fs::{generate_cache_image_path, get_unix_home};
+use crate::monarch_utils::monarch_fs::{generate_cache_image_path, get_unix_home, path_exists};
 use crate::monarch_utils::monarch_settings::get_settings_state;
 use crate::monarch_utils::monarch_state::MONARCH_STATE;
-use crate::monarch_utils::monarch_terminal::run_in_terminal;
+use crate::monarch_utils::monarch_terminal::run_command_in_terminal;
 use crate::monarch_utils::quicklaunch::hide_quicklaunch;
-use crate::{monarch_library::games_library, monarch_utils::monarch_fs};
+use crate::monarch_library::play_history;
+use crate::monarch_library::games_library;
 use anyhow::{bail, Context, Result};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use portable_pty::{CommandBuilder, ExitStatus as PtyExitStatus};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
 use tauri::AppHandle;
 use tracing::{error, info, warn};
 
@@ -26,120 +41,320 @@ pub fn generate_default_folder() -> Result<PathBuf> {
     Ok(path.join("MonarchGames"))
 }
 
-/// Launches a game
-pub async fn launch_game(handle: &AppHandle, frontend_game: &MonarchGame) -> Result<()> {
+/// Launches a game, using the named launch profile or the game's default profile
+pub async fn launch_game(
+    handle: &AppHandle,
+    frontend_game: &MonarchGame,
+    profile_name: Option<&str>,
+) -> Result<()> {
     if let Err(e) = hide_quicklaunch(handle) {
         warn!("monarch_client::launch_game() Error while hiding quicklaunch. Possibly already hidden. | Err: {e}");
     }
 
+    let (game, profile): (MonarchGame, Option<LaunchProfile>) =
+        resolve_game(&frontend_game.id, profile_name)
+            .with_context(|| "monarch_client::launch_game() -> ")?;
+    start_launch(handle, game, profile).await
+}
+
+/// Launches a game again after it crashed, with Proton's logging turned on.
+pub async fn retry_with_logging(handle: &AppHandle, id: &str, profile_name: Option<&str>) -> Result<()> {
+    let (mut game, profile): (MonarchGame, Option<LaunchProfile>) = resolve_game(id, profile_name)
+        .with_context(|| "monarch_client::retry_with_logging() -> ")?;
+    crash_report::enable_logging(&mut game);
+
+    info!("Retrying {} with logging enabled", game.name);
+    start_launch(handle, game, profile).await
+}
+
+/// Runs everything involved in launching a game that has been resolved.
+async fn start_launch(
+    handle: &AppHandle,
+    game: MonarchGame,
+    profile: Option<LaunchProfile>,
+) -> Result<()> {
+    // Also stops the same game from being launched twice, e.g. from quicklaunch
+    game_monitor::register(&game, profile.as_ref())
+        .with_context(|| "monarch_client::launch_game() -> ")?;
+
+    if let Err(e) = run_pre_launch(&game).await {
+        game_monitor::unregister(&game.id);
+        return Err(e);
+    }
+    play_history::record_launch(&game.id);
+
+    if let Err(e) = start_game(handle, game.clone()).await {
+        game_monitor::unregister(&game.id);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Returns a game from the library with its launch profile applied.
+fn resolve_game(id: &str, profile_name: Option<&str>) -> Result<(MonarchGame, Option<LaunchProfile>)> {
     let mut game: MonarchGame;
     unsafe {
         game = MONARCH_STATE
-            .get_game(&frontend_game.id)
-            .with_context(|| "monarch_client::launch_game() -> ")?;
+            .get_game(id)
+            .with_context(|| "monarch_client::resolve_game() -> ")?;
     }
 
-    // Check if game should be launched with exectutable, such as
-    // the game binary or Proton executable
-    if !game.executable_path.is_empty() {
-        info!(
-            "Launching game with executable path: {}",
-            game.executable_path
-        );
-        
-        // Reformat the launch command to work on the platform
-        if cfg!(target_os = "windows") {
-            game.executable_path = format!(r#"Start-Process "{}""#, game.executable_path);
-        } else {
-            game.executable_path = game.executable_path.replace(" ", "\\ ");
-        }
+    let profile: Option<LaunchProfile> = game
+        .get_profile(profile_name)
+        .with_context(|| "monarch_client::resolve_game() -> ")?
+        .cloned();
+    if let Some(profile) = &profile {
+        info!("Using launch profile: {}", profile.name);
+        game.apply_profile(profile);
+    }
+    Ok((game, profile))
+}
 
-        // Run with compatibility layer
-        if !game.compatibility.is_empty() {
-            if cfg!(not(target_os = "linux")) {
-                bail!("monarch_client::launch_game() User tried launching a game using compatibility layer on OS other than Linux! | Err: Cannot use compatibility layer under anything other than Linux!")
-            }
+/// Returns what launching a game would run, without launching it.
+/// Games launched through Steam are shown as the command given to Steam.
+pub fn preview_launch(id: &str, profile_name: Option<&str>) -> Result<LaunchSpec> {
+    let (game, _) = resolve_game(id, profile_name)
+        .with_context(|| "monarch_client::preview_launch() -> ")?;
 
-            #[cfg(target_os = "linux")]
-            return execute_compatibility_game(handle, &mut game).await;
+    if !game.executable_path.is_empty() {
+        return build_launch_spec(&game).with_context(|| "monarch_client::preview_launch() -> ");
+    }
+
+    // Shows what app info would launch if it's cached, without running SteamCMD to get it
+    if game.platform == "steamcmd" {
+        if let Some(info) = steam_appinfo::read_cache(&game.platform_id) {
+            let resolved: MonarchGame = steam_client::resolve_launch(&game, &info)
+                .with_context(|| "monarch_client::preview_launch() -> ")?;
+            return build_launch_spec(&resolved)
+                .with_context(|| "monarch_client::preview_launch() -> ");
         }
+    }
 
-        // Run without compatibility layer
-        let launch_command: String = format!("{}", game.executable_path);
+    let mut spec: LaunchSpec = match game.platform.as_str() {
+        "steam" => LaunchSpec {
+            program: String::from("steam"),
+            args: vec![steam_client::client_launch_uri(&game)],
+            ..Default::default()
+        },
+        "steamcmd" => LaunchSpec {
+            program: String::from("steamcmd"),
+            args: steam_client::cmd_launch_args(&game)
+                .with_context(|| "monarch_client::preview_launch() -> ")?,
+            ..Default::default()
+        },
+        &_ => bail!("monarch_client::preview_launch() | Err: Invalid platform: {}", game.platform),
+    };
+    spec.env = launch_env(&game);
+    Ok(spec)
+}
 
-        // Order launch args and command in proper order
-        let full_command: String = if game.launch_args.find("%command%").is_some() {
-            game.launch_args.replace("%command%", &launch_command)
-        } else {
-            format!("{} {}", launch_command, game.launch_args)
-        };
+/// Runs the global and the game's pre-launch hooks, the game isn't launched if they fail.
+async fn run_pre_launch(game: &MonarchGame) -> Result<()> {
+    let global_hooks: LaunchHooks = get_settings_state().launch.hooks;
+    if game.hooks.pre_launch.is_empty() && global_hooks.pre_launch.is_empty() {
+        return Ok(());
+    }
+    let hooks: LaunchHooks = game.hooks.clone();
+    let context: HookContext = HookContext::from(game);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        launch_hooks::run_pre_launch(&global_hooks, &hooks, &context)
+    })
+    .await
+    .with_context(|| "monarch_client::run_pre_launch() Pre-launch hook panicked! | Err: ")?
+    .with_context(|| "monarch_client::run_pre_launch() -> ")
+}
 
-        return run_in_terminal(handle, &full_command, None)
-            .await
-            .with_context(|| "monarch_client::launch_game() -> ");
+/// Starts a game either via its executable or its platform
+async fn start_game(handle: &AppHandle, game: MonarchGame) -> Result<()> {
+    // Check if game should be launched with exectutable, such as
+    // the game binary or Proton executable
+    if !game.executable_path.is_empty() {
+        return start_executable(handle, &game).await;
     }
 
     // Otherwise launch via platform
+    let env: BTreeMap<String, String> = launch_env(&game);
     match game.platform.as_str() {
         "steam" => {
             info!("Launching game via steam client: {}", game.platform_id);
-            steam_client::launch_client_game(&game)
-                .with_context(|| "monarch_client::launch_game() -> ")
-        }
-        "steamcmd" => {
-            info!("Launching game via steamcmd: {}", game.platform_id);
-            steam_client::launch_cmd_game(handle, &game)
-                .await
+            steam_client::launch_client_game(&game, &env)
                 .with_context(|| "monarch_client::launch_game() -> ")
         }
+        "steamcmd" => match resolve_steamcmd_launch(handle, &game).await {
+            Ok(resolved) => {
+                info!("Launching game from its app info: {}", game.platform_id);
+                start_executable(handle, &resolved).await
+            }
+            // Only native builds can be launched by SteamCMD itself
+            Err(e) if game.platform_override.is_empty() => {
+                warn!("monarch_client::start_game() -> {e:#}");
+                info!("Launching game via steamcmd: {}", game.platform_id);
+                steam_client::launch_cmd_game(handle, &game, &env)
+                    .await
+                    .with_context(|| "monarch_client::launch_game() -> ")
+            }
+            Err(e) => Err(e).with_context(|| "monarch_client::launch_game() -> "),
+        },
         &_ => {
             bail!("monarch_client::launch_game() User tried launching a game on an invalid platform: {} | Err: Invalid platform!", game.platform)
         }
     }
 }
 
-/// Downloads a game into default folder
-pub async fn download_game(
+/// Returns a SteamCMD game set up to launch from disk, getting its app info if not cached.
+async fn resolve_steamcmd_launch(handle: &AppHandle, game: &MonarchGame) -> Result<MonarchGame> {
+    let (info_handle, id) = (handle.clone(), game.platform_id.clone());
+    let info: AppInfo = tauri::async_runtime::spawn_blocking(move || {
+        steam_client::get_app_info(&info_handle, &id)
+    })
+    .await
+    .with_context(|| "monarch_client::resolve_steamcmd_launch() SteamCMD task panicked! | Err: ")?
+    .with_context(|| "monarch_client::resolve_steamcmd_launch() -> ")?;
+
+    steam_client::resolve_launch(game, &info)
+        .with_context(|| "monarch_client::resolve_steamcmd_launch() -> ")
+}
+
+/// Starts a game via its executable, optionally through a compatibility layer.
+async fn start_executable(handle: &AppHandle, game: &MonarchGame) -> Result<()> {
+    info!(
+        "Launching game with executable path: {}",
+        game.executable_path
+    );
+
+    let spec: LaunchSpec = build_launch_spec(game)
+        .with_context(|| "monarch_client::start_executable() -> ")?;
+    info!("Launch command: {spec}");
+    game_monitor::set_launch_spec(&game.id, &spec);
+
+    if !game.show_terminal {
+        return start_detached(game, &spec)
+            .with_context(|| "monarch_client::start_executable() -> ");
+    }
+
+    let result: Result<PtyExitStatus> = run_command_in_terminal(handle, CommandBuilder::from(&spec))
+        .await
+        .with_context(|| "monarch_client::start_executable() -> ");
+    game_monitor::launcher_exited(&game.id, result.as_ref().ok().cloned());
+    result.map(|_| ())
+}
+
+/// Starts a game in the background with its output written to the game's log.
+fn start_detached(game: &MonarchGame, spec: &LaunchSpec) -> Result<()> {
+    if let Err(e) = game_logs::rotate_log(&game.id) {
+        warn!("monarch_client::start_detached() -> {e}");
+    }
+
+    let log: File = game_logs::open_log(&game.id, &format!("Launching: {spec}"))
+        .with_context(|| "monarch_client::start_detached() -> ")?;
+    let log_err: File = log
+        .try_clone()
+        .with_context(|| "monarch_client::start_detached() Failed to clone log file handle! | Err: ")?;
+
+    let mut cmd: Command = Command::from(spec);
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::from(log))
+        .stderr(Stdio::from(log_err));
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let mut child: Child = cmd.spawn().with_context(|| {
+        format!(
+            "monarch_client::start_detached() Failed to start: {} | Err: ",
+            spec.program
+        )
+    })?;
+
+    let id: String = game.id.clone();
+    tauri::async_runtime::spawn_blocking(move || match child.wait() {
+        Ok(status) => {
+            info!("Game process exited with status: {status}");
+            game_monitor::launcher_exited(&id, Some(PtyExitStatus::from(status)));
+        }
+        Err(e) => {
+            error!("monarch_client::start_detached() Failed to wait for game! | Err: {e}");
+            game_monitor::launcher_exited(&id, None);
+        }
+    });
+    Ok(())
+}
+
+/// Adds a game to the download queue to be installed into a game folder,
+/// Monarch's own if none is chosen. A platform override in options downloads the build for
+/// another platform, which is then played through the chosen Proton. A beta branch is
+/// installed if chosen, its password is saved for later updates.
+pub fn download_game(
     handle: &AppHandle,
     name: &str,
     platform: &str,
     platform_id: &str,
-) -> Result<Vec<MonarchGame>> {
-    let mut path: PathBuf = PathBuf::from(get_settings_state().monarch.game_folder);
-
-    if !monarch_fs::path_exists(&path) {
-        monarch_fs::create_dir(&path).with_context(|| "monarch_client::download_game() -> ")?;
+    folder: Option<&str>,
+    chosen: InstallOptions,
+    branch_password: Option<&str>,
+) -> Result<QueuedDownload> {
+    if platform != "steam" {
+        bail!("monarch_client::download_game() Invalid platform!")
     }
-
-    path.push(name); // Game specific path
-    if !monarch_fs::path_exists(&path) {
-        monarch_fs::create_dir(&path).with_context(|| "monarch_client::download_game() -> ")?;
+    if !get_settings_state().steam.manage {
+        bail!("monarch_client::download_game() | Err: Not allowed to manage games. Check settings.")
     }
 
-    let new_game: MonarchGame = match platform {
-        "steam" => {
-            // Check if steamcmd is installed
-            if !steam_client::is_installed() {
-                warn!("monarch_client::download_game() SteamCMD not found!");
-                info!("Attempting to download and install SteamCMD...");
+    let install_dir: PathBuf = steam_client::get_install_dir(folder, name, platform_id)
+        .with_context(|| "monarch_client::download_game() -> ")?;
+    let mut options: InstallOptions = InstallOptions {
+        install_dir: install_dir.display().to_string(),
+        branch: chosen.branch,
+        ..Default::default()
+    };
 
-                steam_client::download_and_install(handle)
-                    .await
-                    .with_context(|| "monarch_client::download_game() -> ")?;
-            }
+    let platform_override: &str = &chosen.platform_override;
+    if !platform_override.is_empty() && platform_override != steamcmd::NATIVE_PLATFORM {
+        options.compatibility = override_compatibility(platform_override, &chosen.compatibility)
+            .with_context(|| "monarch_client::download_game() -> ")?;
+        options.platform_override = platform_override.to_string();
+    }
+    save_branch_password(platform_id, &options.branch, branch_password)
+        .with_context(|| "monarch_client::download_game() -> ")?;
 
-            let mut new_game = steam_client::download_game(handle, name, platform_id)
-                .await
-                .with_context(|| "monarch_client::download_game() -> ")?;
-            new_game.platform = "steamcmd".to_string();
-            new_game
-        }
-        &_ => bail!("monarch_client::download_game() Invalid platform!"),
-    };
+    download_queue::enqueue(handle, name, platform, platform_id, DownloadKind::Install, options)
+        .with_context(|| "monarch_client::download_game() -> ")
+}
 
-    games_library::add_game(&new_game).with_context(|| "monarch_client::download_game() -> ")?;
+/// Returns the Proton a build for another platform is played with.
+/// Only Windows builds can be played that way, and only on Linux.
+fn override_compatibility(platform_override: &str, compatibility: &str) -> Result<String> {
+    if !steamcmd::PLATFORMS.contains(&platform_override) {
+        bail!("monarch_client::override_compatibility() | Err: Invalid platform override: {platform_override}")
+    }
+    if platform_override != "windows" || steamcmd::NATIVE_PLATFORM != "linux" {
+        bail!("monarch_client::override_compatibility() | Err: Builds for {platform_override} can't be played on {}!", steamcmd::NATIVE_PLATFORM)
+    }
+
+    if compatibility.is_empty() {
+        bail!("monarch_client::override_compatibility() | Err: No Proton version chosen to play the Windows build with!")
+    }
+    if !path_exists(Path::new(compatibility)) {
+        bail!("monarch_client::override_compatibility() | Err: Proton not found at: {compatibility}")
+    }
+    Ok(compatibility.to_string())
+}
 
-    Ok(get_library()) // Return new library
+/// Saves the password of a beta branch if one is given.
+fn save_branch_password(platform_id: &str, branch: &str, password: Option<&str>) -> Result<()> {
+    let Some(password) = password.filter(|password| !password.is_empty()) else {
+        return Ok(());
+    };
+    if branch.is_empty() || branch == "public" {
+        bail!("monarch_client::save_branch_password() | Err: Only beta branches have passwords!")
+    }
+    steam_client::set_branch_password(platform_id, branch, password)
+        .with_context(|| "monarch_client::save_branch_password() -> ")
 }
 
 /// Remove an installed game
@@ -147,9 +362,15 @@ pub async fn uninstall_game(handle: &AppHandle, platform: &str, platform_id: &st
     match platform {
         "steam" => {
             steam_client::uninstall_client_game(platform_id)
+                .with_context(|| "monarch_client::uninstall_game() -> ")?;
+
+            match installed_game(platform, platform_id) {
+                Some(game) => remove_game_from_collections(&game.id).with_context(|| "monarch_client::uninstall_game() -> "),
+                None => Ok(()),
+            }
         }
         "steamcmd" => {
-            steam_client::uninstall_game(handle, platform_id)
+            steam_client::uninstall_game(handle, platform_id, installed_dir(platform, platform_id))
             .await
             .with_context(|| "monarch_client::uninstall_game() -> ")?;
 
@@ -157,6 +378,7 @@ pub async fn uninstall_game(handle: &AppHandle, platform: &str, platform_id: &st
 
             for (i, game) in monarch_games.clone().iter().enumerate() {
                 if game.platform == platform && game.platform_id == platform_id {
+                    remove_game_from_collections(&game.id).with_context(|| "monarch_client::uninstall_game() -> ")?;
                     monarch_games.remove(i);
                     unsafe {
                         MONARCH_STATE.set_library_games(&monarch_games);
@@ -174,21 +396,183 @@ pub async fn uninstall_game(handle: &AppHandle, platform: &str, platform_id: &st
     }
 }
 
-/// Update a game
-pub async fn update_game(handle: &AppHandle, platform: &str, platform_id: &str) -> Result<()> {
+/// Adds a game to the download queue to be updated, switching it to another branch if given.
+/// The branch's password is saved for later updates.
+pub fn update_game(
+    handle: &AppHandle,
+    name: &str,
+    platform: &str,
+    platform_id: &str,
+    branch: Option<&str>,
+    branch_password: Option<&str>,
+) -> Result<()> {
     match platform {
         "steam" => {
             bail!("monarch_client::uninstall_game() | Err: Monarch currently does not support updating games from the steam desktop client!")
         }
         "steamcmd" => {
-            steam_client::update_game(handle, platform_id)
-            .await
-            .with_context(|| "monarch_client::uninstall_game() -> ")
+            let mut options: InstallOptions = installed_game(platform, platform_id)
+                .map(|game| update_options(&game))
+                .unwrap_or_default();
+            if let Some(branch) = branch {
+                options.branch = branch.to_string();
+            }
+            save_branch_password(platform_id, &options.branch, branch_password)
+                .with_context(|| "monarch_client::update_game() -> ")?;
+
+            download_queue::enqueue(handle, name, platform, platform_id, DownloadKind::Update, options)
+                .map(|_| ())
+                .with_context(|| "monarch_client::update_game() -> ")
         }
         &_ => bail!("monarch_client::uninstall_game() | Err: Invalid platform passed as argument ( {platform} )")
     }
 }
 
+/// Updates go to the same folder and platform the game was installed for.
+fn update_options(game: &MonarchGame) -> InstallOptions {
+    InstallOptions {
+        install_dir: game.install_dir.clone(),
+        platform_override: game.platform_override.clone(),
+        branch: game.beta_branch.clone(),
+        ..Default::default()
+    }
+}
+
+/// Checks every game installed through SteamCMD for an update and flags the ones that have one.
+/// Games pinned to a manifest are skipped, as they're kept off updates on purpose.
+/// Returns the ids of the games with an update. Blocks while SteamCMD runs.
+pub fn check_for_updates(handle: &AppHandle) -> Result<Vec<String>> {
+    let games: Vec<MonarchGame> = unsafe { MONARCH_STATE.get_library_games() };
+    let mut updates: BTreeMap<String, bool> = BTreeMap::new();
+
+    for game in games
+        .iter()
+        .filter(|game| game.platform == "steamcmd" && game.pinned_manifests.is_empty())
+    {
+        match steam_client::is_update_available(handle, game) {
+            Ok(update_available) => {
+                updates.insert(game.id.clone(), update_available);
+            }
+            Err(e) => warn!("monarch_client::check_for_updates() Failed to check {} for updates! | Err: {e:#}", game.name),
+        }
+    }
+
+    if updates.is_empty() {
+        return Ok(Vec::new());
+    }
+    games_library::set_updates_available(&updates)
+        .with_context(|| "monarch_client::check_for_updates() -> ")?;
+    Ok(updates
+        .into_iter()
+        .filter(|(_, update_available)| *update_available)
+        .map(|(id, _)| id)
+        .collect())
+}
+
+/// Adds every game with an update available to the download queue.
+/// Games pinned to a manifest are left as they are.
+pub fn update_all_games(handle: &AppHandle) -> Result<Vec<QueuedDownload>> {
+    let games: Vec<MonarchGame> = unsafe { MONARCH_STATE.get_library_games() };
+
+    games
+        .iter()
+        .filter(|game| {
+            game.platform == "steamcmd" && game.update_available && game.pinned_manifests.is_empty()
+        })
+        .map(|game| {
+            download_queue::enqueue(
+                handle,
+                &game.name,
+                &game.platform,
+                &game.platform_id,
+                DownloadKind::Update,
+                update_options(game),
+            )
+            .with_context(|| "monarch_client::update_all_games() -> ")
+        })
+        .collect()
+}
+
+/// Adds a manifest of one of a game's depots to the download queue, to be downloaded over the
+/// installed game. The game stays pinned to it until it's updated or unpinned.
+pub fn pin_manifest(
+    handle: &AppHandle,
+    id: &str,
+    depot_id: &str,
+    manifest_id: &str,
+) -> Result<QueuedDownload> {
+    let game: MonarchGame =
+        get_game_details(id).with_context(|| "monarch_client::pin_manifest() -> ")?;
+    if game.platform != "steamcmd" {
+        bail!("monarch_client::pin_manifest() | Err: Only games installed by Monarch can be pinned!")
+    }
+
+    // Both are passed on to SteamCMD, so only plain ids are allowed
+    for value in [depot_id, manifest_id] {
+        if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+            bail!("monarch_client::pin_manifest() | Err: Invalid depot or manifest id: {value}")
+        }
+    }
+
+    let options: InstallOptions = InstallOptions {
+        depot_id: depot_id.to_string(),
+        manifest_id: manifest_id.to_string(),
+        ..update_options(&game)
+    };
+    download_queue::enqueue(
+        handle,
+        &game.name,
+        &game.platform,
+        &game.platform_id,
+        DownloadKind::Pin,
+        options,
+    )
+    .with_context(|| "monarch_client::pin_manifest() -> ")
+}
+
+/// Lets a pinned game be updated by update all again.
+pub fn unpin_game(id: &str) -> Result<()> {
+    games_library::edit_games(|game| {
+        if game.id == id {
+            game.pinned_manifests.clear();
+        }
+    })
+    .with_context(|| "monarch_client::unpin_game() -> ")
+}
+
+/// Returns the folder Monarch installed a game to, None if it's in SteamCMD's own folder.
+fn installed_dir(platform: &str, platform_id: &str) -> Option<String> {
+    installed_game(platform, platform_id)
+        .map(|game| game.install_dir)
+        .filter(|install_dir| !install_dir.is_empty())
+}
+
+fn installed_game(platform: &str, platform_id: &str) -> Option<MonarchGame> {
+    let games: Vec<MonarchGame> = unsafe { MONARCH_STATE.get_library_games() };
+    games
+        .into_iter()
+        .find(|game| game.platform == platform && game.platform_id == platform_id)
+}
+
+/// Returns launch profiles of a game, with the default profile first.
+pub fn get_launch_profiles(id: &str) -> Result<Vec<LaunchProfile>> {
+    let game: MonarchGame =
+        get_game_details(id).with_context(|| "monarch_client::get_launch_profiles() -> ")?;
+
+    let mut profiles: Vec<LaunchProfile> = game.launch_profiles;
+    profiles.sort_by_key(|profile| profile.name != game.default_profile);
+    Ok(profiles)
+}
+
+/// Returns everything Monarch knows about a game in the library.
+pub fn get_game_details(id: &str) -> Result<MonarchGame> {
+    unsafe {
+        MONARCH_STATE.get_game(id).with_context(|| {
+            format!("monarch_client::get_game_details() | Err: No game with id: {id} in library!")
+        })
+    }
+}
+
 /// Returns games found in library.json
 pub fn get_library() -> Vec<MonarchGame> {
     let mut games: Vec<MonarchGame> = Vec::new();
@@ -224,13 +608,22 @@ pub async fn refresh_library() -> Vec<MonarchGame> {
 
     games.append(&mut steam_games);
 
+    let previous_games: Vec<MonarchGame>;
     unsafe {
+        previous_games = MONARCH_STATE.get_library_games();
         MONARCH_STATE.set_library_games(&games);
 
         // Replace games with the updated list of library games
         games = MONARCH_STATE.get_library_games();
     }
 
+    // Games that have left the library, such as ones uninstalled outside of Monarch
+    for game in previous_games.iter().filter(|game| !games.iter().any(|g| g.id == game.id)) {
+        if let Err(e) = remove_game_from_collections(&game.id) {
+            error!("monarch_client::refresh_library() -> {e}");
+        }
+    }
+
     if let Err(e) = games_library::write_games(&games) {
         error!("monarch_client::refresh_library() -> {e}");
     }
@@ -260,44 +653,102 @@ pub async fn find_games(search_term: &str) -> Vec<MonarchGame> {
         let mut new_monarchgame = MonarchGame::from(&game);
         new_monarchgame.thumbnail_path = thumbnail_path;
         new_monarchgame.download_thumbnail(game.cover_url).await; // Do not await, this allows image to download concurrently as other monarchgames are parsed
+        new_monarchgame.download_artwork(game.artwork_url).await;
         monarch_games.push(new_monarchgame);
     }
 
     monarch_games
 }
 
+/// A command to run along with the environment variables it needs.
+type CommandWithEnv = (Vec<String>, Vec<(String, String)>);
+
+/// Builds the command line for a game launched through its executable,
+/// optionally through a compatibility layer such as Proton.
+fn build_launch_spec(game: &MonarchGame) -> Result<LaunchSpec> {
+    let (command, compat_env): CommandWithEnv =
+        if game.compatibility.is_empty() {
+            (vec![game.executable_path.clone()], Vec::new())
+        } else {
+            compatibility_command(game).with_context(|| "monarch_client::build_launch_spec() -> ")?
+        };
+
+    info!("Launch args: {}", game.launch_args);
+    let mut spec: LaunchSpec = LaunchSpec::new(command, &game.launch_args)
+        .with_context(|| "monarch_client::build_launch_spec() -> ")?;
+
+    // Variables from launch args take precedence over the game's and Proton's
+    let env: BTreeMap<String, String> = launch_env(game);
+    spec.default_env(env.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+    spec.default_env(compat_env.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+
+    // Games often expect to be run from their own folder
+    spec.cwd = if game.working_dir.is_empty() {
+        PathBuf::from(&game.executable_path)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(PathBuf::from)
+    } else {
+        Some(PathBuf::from(&game.working_dir))
+    };
+
+    let wrappers: LaunchWrappers = game.wrappers.or(&get_settings_state().launch.wrappers);
+    wrappers
+        .apply(&mut spec)
+        .with_context(|| "monarch_client::build_launch_spec() -> ")?;
+
+    // Tag the game so the monitor can find all of its processes
+    spec.set_env(GAME_ID_ENV, &game.id);
+    Ok(spec)
+}
+
+/// Environment variables set for a game, the game's own on top of the defaults in settings.
+fn launch_env(game: &MonarchGame) -> BTreeMap<String, String> {
+    let mut env: BTreeMap<String, String> =
+        get_settings_state().launch.env.into_iter().collect();
+    env.extend(game.env.clone());
+    env
+}
+
+/// Command and environment variables for running a game through a compatibility layer,
+/// such as Proton, outside of Steam.
 #[cfg(target_os = "linux")]
-async fn execute_compatibility_game(handle: &AppHandle, game: &mut MonarchGame) -> Result<()> {
+fn compatibility_command(game: &MonarchGame) -> Result<CommandWithEnv> {
     use super::linux;
-
+    use crate::monarch_utils::monarch_fs::{create_dir, get_compatdata_path};
     info!("Compatibility layer set: {}", game.compatibility);
-    game.compatibility = game.compatibility.replace(" ", "\\ ");
 
     let compat_client_install_dir = linux::steam::get_default_location()
-        .with_context(|| "monarch_client::launch_game() -> ")?;
-    let compatdata_dir = compat_client_install_dir.join("steamapps/compatdata");
+        .with_context(|| "monarch_client::compatibility_command() -> ")?;
+    let mut compatdata_dir: PathBuf = compat_client_install_dir.join("steamapps/compatdata");
+    let mut env: Vec<(String, String)> = Vec::new();
 
-    let compat_client_install_dir_str = compat_client_install_dir.to_str().unwrap_or("");
-    let compatdata_dir_str = compatdata_dir.to_str().unwrap_or("");
-    let env_vars: HashMap<&str, &str> = HashMap::from([
-        (
-            "STEAM_COMPAT_CLIENT_INSTALL_PATH",
-            compat_client_install_dir_str,
-        ),
-        ("STEAM_COMPAT_DATA_PATH", compatdata_dir_str),
-    ]);
+    // Builds downloaded for Windows by Monarch get a prefix of their own
+    if !game.platform_override.is_empty() {
+        compatdata_dir = get_compatdata_path(&game.platform_id);
+        create_dir(&compatdata_dir).with_context(|| "monarch_client::compatibility_command() -> ")?;
 
-    let launch_command: String = format!("{} run {}", game.compatibility, game.executable_path);
+        // Lets Proton apply its fixes for the game
+        env.push((String::from("SteamAppId"), game.platform_id.clone()));
+    }
 
-    // Order launch args and command in proper order
-    info!("Launch args: {}", game.launch_args);
-    let full_command: String = if game.launch_args.find("%command%").is_some() {
-        game.launch_args.replace("%command%", &launch_command)
-    } else {
-        format!("{} {}", launch_command, game.launch_args)
-    };
+    let command: Vec<String> = vec![
+        game.compatibility.clone(),
+        String::from("run"),
+        game.executable_path.clone(),
+    ];
+    env.push((
+        String::from("STEAM_COMPAT_CLIENT_INSTALL_PATH"),
+        compat_client_install_dir.display().to_string(),
+    ));
+    env.push((
+        String::from("STEAM_COMPAT_DATA_PATH"),
+        compatdata_dir.display().to_string(),
+    ));
+    Ok((command, env))
+}
 
-    run_in_terminal(handle, &full_command, Some(env_vars))
-        .await
-        .with_context(|| "monarch_client::launch_game() -> ")
+#[cfg(not(target_os = "linux"))]
+fn compatibility_command(_game: &MonarchGame) -> Result<CommandWithEnv> {
+    bail!("monarch_client::compatibility_command() User tried launching a game using compatibility layer on OS other than Linux! | Err: Cannot use compatibility layer under anything other than Linux!")
 }
@@ -1,11 +1,15 @@
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use tracing::error;
 
+use super::launch_hooks::LaunchHooks;
+use super::launch_wrappers::LaunchWrappers;
 use crate::monarch_utils::monarch_download::download_image;
-use crate::monarch_utils::monarch_fs::path_exists;
+use crate::monarch_utils::monarch_fs::{generate_artwork_path, path_exists};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct MonarchGame {
     pub name: String,
     pub id: String, // Monach ID, generated by monarch-launcher.com
@@ -16,6 +20,101 @@ pub struct MonarchGame {
     pub launch_args: String,
     pub compatibility: String,
     pub store_page: String,
+
+    // Metadata from monarch-launcher.com, missing in older library files
+    #[serde(default)]
+    pub summary: String,
+    #[serde(default)]
+    pub artwork_path: String,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    #[serde(default)]
+    pub developer: String,
+    #[serde(default)]
+    pub publisher: String,
+    #[serde(default)]
+    pub release_date: String,
+    #[serde(default)]
+    pub igdb_id: Option<i32>,
+
+    // Set by the user, the real values are kept in user_data.json
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub favorite: bool,
+    #[serde(default)]
+    pub hidden: bool,
+
+    // Other entries of the same game, when entries from several sources have been merged
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub installations: Vec<Installation>,
+
+    // Alternative ways to launch the game, such as DX11 vs Vulkan or modded vs vanilla
+    #[serde(default)]
+    pub launch_profiles: Vec<LaunchProfile>,
+    #[serde(default)]
+    pub default_profile: String, // Name of profile used when none is chosen, empty for none
+
+    // Programs such as gamescope or MangoHud to run the game through
+    #[serde(default)]
+    pub wrappers: LaunchWrappers,
+
+    #[serde(default)]
+    pub working_dir: String, // Empty for the executable's folder
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+
+    #[serde(default)]
+    pub hooks: LaunchHooks,
+    #[serde(default)]
+    pub show_terminal: bool, // Launch in the Monarch terminal instead of in the background
+
+    #[serde(default)]
+    pub install_dir: String, // Folder Monarch installed the game to, empty for SteamCMD's own
+    #[serde(default)]
+    pub platform_override: String, // Platform SteamCMD downloads builds for, empty for the native one
+    #[serde(default)]
+    pub update_available: bool, // Set by the periodic update check of SteamCMD games
+    #[serde(default)]
+    pub beta_branch: String, // Steam branch installed through SteamCMD, empty for public
+    #[serde(default)]
+    pub pinned_manifests: HashMap<String, String>, // Depot id -> manifest id, skipped by update all
+}
+
+/// A named way to launch a game. Empty fields fall back to the game's own values.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct LaunchProfile {
+    pub name: String,
+    pub executable_path: String,
+    pub launch_args: String,
+    pub working_dir: String,
+    pub env: HashMap<String, String>,
+    pub compatibility: String, // Runner, such as a Proton version
+    pub pre_launch: String,    // Command to run before the game starts
+    pub post_exit: String,     // Command to run after the game exits
+}
+
+/// One of the entries a merged game consists of.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Installation {
+    pub id: String,
+    pub name: String,
+    pub platform: String,
+    pub platform_id: String,
+    pub executable_path: String,
+}
+
+impl From<&MonarchGame> for Installation {
+    fn from(game: &MonarchGame) -> Self {
+        Self {
+            id: game.id.clone(),
+            name: game.name.clone(),
+            platform: game.platform.clone(),
+            platform_id: game.platform_id.clone(),
+            executable_path: game.executable_path.clone(),
+        }
+    }
 }
 
 impl MonarchGame {
@@ -38,6 +137,7 @@ impl MonarchGame {
             launch_args: String::new(),
             compatibility: String::new(),
             store_page: store_page.to_string(),
+            ..Default::default()
         }
     }
 
@@ -69,6 +169,91 @@ impl MonarchGame {
         }
     }
 
+    /// Download artwork for MonarchGame, stored next to the thumbnail
+    pub async fn download_artwork(&mut self, url: String) {
+        if url.is_empty() || self.thumbnail_path.is_empty() {
+            return;
+        }
+
+        let path: PathBuf = generate_artwork_path(&PathBuf::from(&self.thumbnail_path));
+        self.artwork_path = path.to_str().unwrap_or_default().to_string();
+
+        if path_exists(&path) {
+            return;
+        }
+
+        if let Err(e) = download_image(&url, &path).await {
+            error!("monarchgame::download_artwork() -> {e}");
+            self.artwork_path = String::new();
+        }
+    }
+
+    /// Returns the profile to launch with, which is the named one if given and otherwise the
+    /// default profile. None means the game is launched without a profile.
+    pub fn get_profile(&self, name: Option<&str>) -> Result<Option<&LaunchProfile>> {
+        let name: &str = match name {
+            Some(name) => name,
+            None if !self.default_profile.is_empty() => &self.default_profile,
+            None => return Ok(None),
+        };
+
+        match self.launch_profiles.iter().find(|profile| profile.name == name) {
+            Some(profile) => Ok(Some(profile)),
+            None => bail!(
+                "monarchgame::get_profile() | Err: {} has no launch profile called: {name}",
+                self.name
+            ),
+        }
+    }
+
+    /// Overrides executable, arguments, runner, working directory and hooks with the ones set
+    /// in a profile. Environment variables of the profile are added to the game's.
+    pub fn apply_profile(&mut self, profile: &LaunchProfile) {
+        if !profile.executable_path.is_empty() {
+            self.executable_path = profile.executable_path.clone();
+        }
+        if !profile.launch_args.is_empty() {
+            self.launch_args = profile.launch_args.clone();
+        }
+        if !profile.compatibility.is_empty() {
+            self.compatibility = profile.compatibility.clone();
+        }
+        if !profile.working_dir.is_empty() {
+            self.working_dir = profile.working_dir.clone();
+        }
+        if !profile.pre_launch.is_empty() {
+            self.hooks.pre_launch = profile.pre_launch.clone();
+        }
+        if !profile.post_exit.is_empty() {
+            self.hooks.post_exit = profile.post_exit.clone();
+        }
+        self.env.extend(profile.env.clone());
+    }
+
+    /// Checks that profile names are unique and the default profile exists.
+    pub fn validate_profiles(&self) -> Result<()> {
+        let mut names: HashSet<&str> = HashSet::new();
+        for profile in self.launch_profiles.iter() {
+            if profile.name.trim().is_empty() {
+                bail!("monarchgame::validate_profiles() | Err: Launch profiles need a name!")
+            }
+            if !names.insert(&profile.name) {
+                bail!(
+                    "monarchgame::validate_profiles() | Err: More than one launch profile called: {}",
+                    profile.name
+                )
+            }
+        }
+
+        if !self.default_profile.is_empty() && !names.contains(self.default_profile.as_str()) {
+            bail!(
+                "monarchgame::validate_profiles() | Err: Default profile: {} doesn't exist!",
+                self.default_profile
+            )
+        }
+        Ok(())
+    }
+
     /// Convert MonarchWebGame to MonarchGame
     pub fn from(other: &MonarchWebGame) -> Self {
         Self {
@@ -81,6 +266,14 @@ impl MonarchGame {
             launch_args: "".to_string(),
             compatibility: "".to_string(),
             store_page: other.store_page.to_string(),
+            summary: other.summary.to_string(),
+            artwork_path: "".to_string(),
+            genres: other.genres.clone(),
+            developer: other.developer.to_string(),
+            publisher: other.publisher.to_string(),
+            release_date: other.release_date.to_string(),
+            igdb_id: Some(other.igdb_id).filter(|id| *id > 0),
+            ..Default::default()
         }
     }
 }
@@ -115,4 +308,12 @@ pub struct MonarchWebGame {
     pub platform: String,
     pub platform_id: String,
     pub store_page: String,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    #[serde(default)]
+    pub developer: String,
+    #[serde(default)]
+    pub publisher: String,
+    #[serde(default)]
+    pub release_date: String,
 }
@@ -0,0 +1,407 @@
+/*
+* App info of Steam games, as printed by SteamCMD's app_info_print.
+*
+* The printed text KeyValues are turned into an AppInfo holding what Monarch needs: the folder
+* a game installs to, how it's launched on each platform, its depots and its branches.
+* App info is cached in monarch_home, one JSON file per game, so launching a game doesn't need
+* SteamCMD. The cache is refreshed whenever Monarch installs or updates the game.
+*/
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use tracing::warn;
+
+use crate::monarch_library::play_history::unix_now;
+use crate::monarch_utils::monarch_fs::{
+    create_dir, get_appinfo_cache_path, path_exists, write_json_content,
+};
+use crate::monarch_utils::monarch_vdf::{parse_keyvalues, KeyValues};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct AppInfo {
+    pub id: String,
+    pub name: String,
+    pub install_dir: String, // Folder name in steamapps/common
+    pub launch: Vec<LaunchConfig>,
+    pub depots: Vec<Depot>,
+    pub branches: Vec<Branch>,
+    pub fetched_at: u64,
+}
+
+/// A way of launching a game, from the app info's config/launch.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct LaunchConfig {
+    pub executable: String, // Relative to the install folder
+    pub arguments: String,
+    pub working_dir: String, // Relative to the install folder, empty for the executable's folder
+    pub kind: String,        // Such as default or option, empty if not given
+    pub description: String,
+    pub oslist: Vec<String>, // Empty for every platform
+    pub osarch: String,      // 32 or 64, empty for either
+    pub beta_key: String,    // Branch the config is limited to, empty for all
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Depot {
+    pub id: String,
+    pub name: String,
+    pub oslist: Vec<String>,                 // Empty for every platform
+    pub manifests: BTreeMap<String, String>, // Branch name -> manifest id
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Branch {
+    pub name: String,
+    pub build_id: String,
+    pub description: String,
+    pub password_required: bool,
+    pub time_updated: u64,
+}
+
+impl AppInfo {
+    /// Parses the output of app_info_print for a game.
+    pub fn parse(id: &str, lines: &[String]) -> Result<Self> {
+        // The app info starts at a line holding only its quoted id and ends at its closing brace
+        let start_line: String = format!("\"{id}\"");
+        let Some(start) = lines.iter().position(|line| line.trim() == start_line) else {
+            bail!("steam_appinfo::AppInfo::parse() | Err: No app info printed for: {id}")
+        };
+
+        let mut depth: i32 = 0;
+        let mut text: String = String::new();
+        for line in &lines[start..] {
+            text.push_str(line);
+            text.push('\n');
+
+            match line.trim() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => continue,
+            }
+            if depth == 0 {
+                break;
+            }
+        }
+
+        let root: KeyValues =
+            parse_keyvalues(&text).with_context(|| "steam_appinfo::AppInfo::parse() -> ")?;
+        let Some(app) = root.get(id) else {
+            bail!("steam_appinfo::AppInfo::parse() | Err: No app info printed for: {id}")
+        };
+        Ok(Self::from_keyvalues(id, app))
+    }
+
+    fn from_keyvalues(id: &str, app: &KeyValues) -> Self {
+        let empty: KeyValues = KeyValues::Object(Vec::new());
+        let config: &KeyValues = app.get("config").unwrap_or(&empty);
+        let depots: &KeyValues = app.get("depots").unwrap_or(&empty);
+
+        let launch: Vec<LaunchConfig> = config
+            .get("launch")
+            .unwrap_or(&empty)
+            .entries()
+            .iter()
+            .map(|(_, launch)| {
+                let launch_config: &KeyValues = launch.get("config").unwrap_or(&empty);
+                LaunchConfig {
+                    executable: string(launch, "executable"),
+                    arguments: string(launch, "arguments"),
+                    working_dir: string(launch, "workingdir"),
+                    kind: string(launch, "type"),
+                    description: string(launch, "description"),
+                    oslist: oslist(launch_config),
+                    osarch: string(launch_config, "osarch"),
+                    beta_key: string(launch_config, "betakey"),
+                }
+            })
+            .collect();
+
+        // Depots are listed by id, next to keys such as branches
+        let depot_list: Vec<Depot> = depots
+            .entries()
+            .iter()
+            .filter(|(id, depot)| {
+                id.chars().all(|c| c.is_ascii_digit()) && matches!(depot, KeyValues::Object(_))
+            })
+            .map(|(id, depot)| Depot {
+                id: id.clone(),
+                name: string(depot, "name"),
+                oslist: oslist(depot.get("config").unwrap_or(&empty)),
+                manifests: depot
+                    .get("manifests")
+                    .unwrap_or(&empty)
+                    .entries()
+                    .iter()
+                    .filter_map(|(branch, manifest)| {
+                        // Older app info gives the manifest id directly instead of its gid
+                        let gid: &str = manifest.as_str().or_else(|| manifest.get_str("gid"))?;
+                        Some((branch.clone(), gid.to_string()))
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let branches: Vec<Branch> = depots
+            .get("branches")
+            .unwrap_or(&empty)
+            .entries()
+            .iter()
+            .map(|(name, branch)| Branch {
+                name: name.clone(),
+                build_id: string(branch, "buildid"),
+                description: string(branch, "description"),
+                password_required: branch.get_str("pwdrequired") == Some("1"),
+                time_updated: string(branch, "timeupdated").parse().unwrap_or(0),
+            })
+            .collect();
+
+        Self {
+            id: id.to_string(),
+            name: app
+                .get("common")
+                .and_then(|common| common.get_str("name"))
+                .unwrap_or_default()
+                .to_string(),
+            install_dir: string(config, "installdir"),
+            launch,
+            depots: depot_list,
+            branches,
+            fetched_at: unix_now(),
+        }
+    }
+
+    /// Returns the launch config a game is started with on a platform: the first one made for
+    /// it, or for every platform, that isn't limited to a beta branch. Default configs and
+    /// 64 bit ones are preferred.
+    pub fn launch_config(&self, platform: &str) -> Option<&LaunchConfig> {
+        self.launch
+            .iter()
+            .filter(|config| config.beta_key.is_empty() && !config.executable.is_empty())
+            .filter(|config| {
+                config.oslist.is_empty() || config.oslist.iter().any(|os| os == platform)
+            })
+            .min_by_key(|config| {
+                let is_default: bool = config.kind.is_empty() || config.kind == "default";
+                (!is_default, config.osarch == "32")
+            })
+    }
+
+    /// Returns the branch with the given name, such as public.
+    pub fn branch(&self, name: &str) -> Option<&Branch> {
+        self.branches.iter().find(|branch| branch.name == name)
+    }
+}
+
+/// Returns the cached app info of a game, None if it isn't cached.
+pub fn read_cache(id: &str) -> Option<AppInfo> {
+    let path: PathBuf = get_appinfo_cache_path(id);
+    if !path_exists(&path) {
+        return None;
+    }
+
+    let content: String = fs::read_to_string(&path)
+        .map_err(|e| {
+            warn!(
+                "steam_appinfo::read_cache() Failed to read: {} | Err: {e}",
+                path.display()
+            )
+        })
+        .ok()?;
+    serde_json::from_str(&content)
+        .map_err(|e| {
+            warn!(
+                "steam_appinfo::read_cache() Failed to parse: {} | Err: {e}",
+                path.display()
+            )
+        })
+        .ok()
+}
+
+pub fn write_cache(info: &AppInfo) -> Result<()> {
+    let path: PathBuf = get_appinfo_cache_path(&info.id);
+    if let Some(dir) = path.parent() {
+        create_dir(dir).with_context(|| "steam_appinfo::write_cache() -> ")?;
+    }
+    write_json_content(json!(info), &path).with_context(|| "steam_appinfo::write_cache() -> ")
+}
+
+fn string(keyvalues: &KeyValues, key: &str) -> String {
+    keyvalues.get_str(key).unwrap_or_default().to_string()
+}
+
+/// Platforms in an oslist such as "windows,macos".
+fn oslist(config: &KeyValues) -> Vec<String> {
+    config
+        .get_str("oslist")
+        .unwrap_or_default()
+        .split(',')
+        .map(|os| os.trim().to_string())
+        .filter(|os| !os.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output of app_info_print 440, trimmed down.
+    const PRINTED: &str = r#"AppID : 440, change number : 24077349/0, last change : Mon Jun  3 18:10:22 2024
+"440"
+{
+	"common"
+	{
+		"name"		"Team Fortress 2"
+	}
+	"config"
+	{
+		"installdir"		"Team Fortress 2"
+		"launch"
+		{
+			"0"
+			{
+				"executable"		"tf_win64.exe"
+				"arguments"		"-game tf"
+				"config"
+				{
+					"oslist"		"windows"
+				}
+			}
+			"1"
+			{
+				"executable"		"tf.sh"
+				"arguments"		"-game tf -steam"
+				"type"		"option"
+				"config"
+				{
+					"oslist"		"linux"
+				}
+			}
+			"2"
+			{
+				"executable"		"tf_linux32.sh"
+				"type"		"default"
+				"config"
+				{
+					"oslist"		"linux"
+					"osarch"		"32"
+				}
+			}
+			"3"
+			{
+				"executable"		"tf_linux64.sh"
+				"workingdir"		"bin\linux64"
+				"type"		"default"
+				"config"
+				{
+					"oslist"		"linux,macos"
+					"osarch"		"64"
+				}
+			}
+			"4"
+			{
+				"executable"		"tf_beta.sh"
+				"config"
+				{
+					"betakey"		"prerelease"
+				}
+			}
+		}
+	}
+	"depots"
+	{
+		"441"
+		{
+			"name"		"Team Fortress 2 Content"
+			"config"
+			{
+				"oslist"		"windows, linux"
+			}
+			"manifests"
+			{
+				"public"
+				{
+					"gid"		"7240385658394928113"
+					"size"		"17526409128"
+				}
+				"old"		"1234567890"
+			}
+		}
+		"baselanguages"		"english"
+		"branches"
+		{
+			"public"
+			{
+				"buildid"		"14727218"
+				"timeupdated"		"1717438142"
+			}
+			"prerelease"
+			{
+				"buildid"		"14727300"
+				"description"		"Upcoming changes"
+				"pwdrequired"		"1"
+			}
+		}
+	}
+}
+Steam>"#;
+
+    fn printed_lines() -> Vec<String> {
+        PRINTED.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn parse_reads_printed_app_info() {
+        let info: AppInfo = AppInfo::parse("440", &printed_lines()).unwrap();
+
+        assert_eq!(info.name, "Team Fortress 2");
+        assert_eq!(info.install_dir, "Team Fortress 2");
+        assert_eq!(info.launch.len(), 5);
+        assert_eq!(info.launch[3].working_dir, r"bin\linux64");
+        assert_eq!(info.launch[3].oslist, ["linux", "macos"]);
+
+        assert_eq!(info.depots.len(), 1);
+        let depot: &Depot = &info.depots[0];
+        assert_eq!(depot.oslist, ["windows", "linux"]);
+        assert_eq!(
+            depot.manifests.get("public").map(String::as_str),
+            Some("7240385658394928113")
+        );
+        assert_eq!(
+            depot.manifests.get("old").map(String::as_str),
+            Some("1234567890")
+        );
+
+        let beta: &Branch = info.branch("prerelease").unwrap();
+        assert_eq!(beta.build_id, "14727300");
+        assert!(beta.password_required);
+        assert_eq!(info.branch("public").unwrap().time_updated, 1717438142);
+        assert!(!info.branch("public").unwrap().password_required);
+    }
+
+    #[test]
+    fn launch_config_prefers_default_64_bit_configs() {
+        let info: AppInfo = AppInfo::parse("440", &printed_lines()).unwrap();
+
+        let executable = |platform: &str| {
+            info.launch_config(platform)
+                .map(|config| config.executable.as_str())
+        };
+        assert_eq!(executable("linux"), Some("tf_linux64.sh"));
+        assert_eq!(executable("macos"), Some("tf_linux64.sh"));
+        assert_eq!(executable("windows"), Some("tf_win64.exe"));
+    }
+
+    #[test]
+    fn parse_fails_without_the_app() {
+        assert!(AppInfo::parse("570", &printed_lines()).is_err());
+        assert!(AppInfo::parse("440", &[String::from("Steam>")]).is_err());
+    }
+}
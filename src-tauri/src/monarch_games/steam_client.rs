@@ -1,19 +1,29 @@
 use anyhow::{bail, Context, Result};
 use reqwest;
 use scraper::{Html, Selector};
+use portable_pty::ChildKiller;
 use serde_json::Value;
 use simple_steam_totp::generate;
+use std::collections::BTreeMap;
+use std::fs;
 use std::path::PathBuf;
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter};
 use tokio::task;
 use tracing::{error, info, warn};
 
+use super::download_queue::InstallOptions;
+use super::launch_spec::{quote_shell_word, split_shell_words};
+use super::steam_appinfo::{self, AppInfo};
+use super::steamcmd::{self, InstallTarget, LoginAnswers, SteamCmdOutput, UpdateProgress};
+use super::steamcmd_session::{self, SessionUnavailable};
 use super::monarchgame::{MonarchGame, MonarchWebGame};
-use crate::monarch_utils::monarch_credentials::get_password;
+use crate::monarch_utils::monarch_credentials::{get_password, set_credentials};
 use crate::monarch_utils::monarch_fs::{
-    generate_cache_image_path, generate_library_image_path, get_monarch_home, path_exists,
+    copy_dir, generate_cache_image_path, generate_library_image_path, get_monarch_home,
+    path_exists,
 };
 use crate::monarch_utils::monarch_settings::{get_settings_state, LauncherSettings};
+use crate::monarch_utils::monarch_vdf::{parse_keyvalues, KeyValues};
 
 #[cfg(target_os = "windows")]
 use super::windows::steam;
@@ -49,128 +59,422 @@ pub async fn get_library() -> Vec<MonarchGame> {
 }
 
 /// Attempts to launch Steam Client game.
-pub fn launch_client_game(game: &MonarchGame) -> Result<()> {
-    let command: String = format!("steam://rungameid/{}", &game.platform_id);
-    steam::run_command(&command).with_context(|| "steam_client::launch_game() -> ")
+/// Environment variables only reach the game if Steam isn't already running.
+pub fn launch_client_game(game: &MonarchGame, env: &BTreeMap<String, String>) -> Result<()> {
+    let command: String = client_launch_uri(game);
+    steam::run_command(&command, env).with_context(|| "steam_client::launch_game() -> ")
+}
+
+/// Returns the steam:// link that launches a game through Steam Client.
+pub fn client_launch_uri(game: &MonarchGame) -> String {
+    format!("steam://rungameid/{}", &game.platform_id)
 }
 
 /// Attempts to uninstall a Steam Client game.
 pub fn uninstall_client_game(id: &str) -> Result<()> {
     let mut command: String = String::from("steam://uninstall/");
     command.push_str(id);
-    steam::run_command(&command).with_context(|| "steam_client::launch_game() -> ")
+    steam::run_command(&command, &BTreeMap::new())
+        .with_context(|| "steam_client::uninstall_client_game() -> ")
 }
 
 /// Attemps to launch SteamCMD game.
-pub async fn launch_cmd_game(handle: &AppHandle, game: &MonarchGame) -> Result<()> {
+pub async fn launch_cmd_game(
+    handle: &AppHandle,
+    game: &MonarchGame,
+    env: &BTreeMap<String, String>,
+) -> Result<()> {
     let settings = get_settings_state();
     let steam_settings = settings.steam;
-    let login_arg = get_steamcmd_login(&steam_settings)?;
+    let login_arg: String = get_steamcmd_login(&steam_settings);
+    let launch_args: String = cmd_launch_args(game)
+        .with_context(|| "steam_client::launch_cmd_game() -> ")?
+        .iter()
+        .map(|arg| quote_shell_word(arg))
+        .collect::<Vec<String>>()
+        .join(" ");
 
     let args: Vec<&str> = vec![
         "+@ShutdownOnFailedCommand 1",
         &login_arg,
-        "+app_launch",
-        &game.platform_id,
-        &game.launch_args,
+        &launch_args,
         "+quit",
     ];
 
-    steam::steamcmd_command(handle, args)
+    steam::steamcmd_command(handle, args, env)
         .await
         .with_context(|| "steam_client::launch_cmd_game() -> ")
 }
 
-/// Download a Steam game via Monarch and SteamCMD.
-pub async fn download_game(handle: &AppHandle, name: &str, id: &str) -> Result<MonarchGame> {
-    let settings = get_settings_state();
-    let steam_settings = settings.steam;
+/// Returns the SteamCMD arguments that launch a game, without logging in.
+pub fn cmd_launch_args(game: &MonarchGame) -> Result<Vec<String>> {
+    let mut args: Vec<String> = vec![String::from("+app_launch"), game.platform_id.clone()];
+    args.extend(
+        split_shell_words(&game.launch_args)
+            .with_context(|| "steam_client::cmd_launch_args() -> ")?,
+    );
+    Ok(args)
+}
+
+/// Installs or updates a Steam game via SteamCMD without showing it, reporting progress as
+/// SteamCMD prints it. Games are installed to the target's install_dir if given, otherwise to
+/// SteamCMD's own folder, as the build for the target's platform. The build comes from branch,
+/// or from the public branch if it's empty, using the branch's password if one is saved.
+/// on_spawn gets a handle that can kill SteamCMD, used to cancel.
+/// Blocks until the update is done.
+pub fn app_update(
+    handle: &AppHandle,
+    id: &str,
+    target: &InstallTarget,
+    branch: &str,
+    on_spawn: impl FnMut(Box<dyn ChildKiller + Send + Sync>),
+    mut on_progress: impl FnMut(UpdateProgress),
+) -> Result<()> {
+    // Also given for the public branch, to move games off the beta they were on
+    let branch: &str = if branch.is_empty() { "public" } else { branch };
+    let mut commands: Vec<String> = vec![
+        String::from("app_update"),
+        id.to_string(),
+        String::from("-beta"),
+        branch.to_string(),
+    ];
+    if let Some(password) = get_branch_password(id, branch) {
+        commands.push(String::from(steamcmd::BETA_PASSWORD_FLAG));
+        commands.push(password);
+    }
+    commands.push(String::from("validate"));
 
+    run_steamcmd(handle, target, commands, on_spawn, |line| {
+        if let Some(progress) = steamcmd::parse_progress(line) {
+            on_progress(progress);
+        }
+    })
+    .with_context(|| "steam_client::app_update() -> ")
+}
+
+/// Runs a SteamCMD command, given as separate words, in the background. Uses the account's
+/// SteamCMD session, or a SteamCMD of its own if no session can be started.
+/// Commands about an installed game need the install_dir it was installed to, to find it.
+/// Builds are downloaded for the target's platform.
+/// Fails with a SteamCmdError if SteamCMD reports a known failure. Blocks until it's done.
+/// Emits steam-guard-required when Steam asks for a Steam Guard code.
+fn run_steamcmd(
+    handle: &AppHandle,
+    target: &InstallTarget,
+    commands: Vec<String>,
+    mut on_spawn: impl FnMut(Box<dyn ChildKiller + Send + Sync>),
+    mut on_line: impl FnMut(&str),
+) -> Result<()> {
+    let steam_settings = get_settings_state().steam;
     if !steam_settings.manage {
-        warn!("steam_client::download_game() User tried to install game without allowing Monarch to manage Steam! Cancelling download...");
-        bail!("steam_client::download_game() | Err: Not allowed to manage games. Check settings.")
+        warn!("steam_client::run_steamcmd() User tried to use SteamCMD without allowing Monarch to manage Steam! Cancelling...");
+        bail!("steam_client::run_steamcmd() | Err: Not allowed to manage games. Check settings.")
     }
 
-    let mut install_dir: PathBuf = PathBuf::from(settings.monarch.game_folder);
-    let sanitized_name: String = name.replace(" ", "\\ ");
-    install_dir.push(sanitized_name);
+    let mut on_line = |line: &str| {
+        if let Some(prompt) = steamcmd::parse_guard_prompt(line) {
+            info!("Steam is asking for Steam Guard: {:?}", prompt);
+            if let Err(e) = handle.emit("steam-guard-required", &prompt) {
+                error!("steam_client::run_steamcmd() Failed to emit steam-guard-required! | Err: {e}");
+            }
+        }
+        on_line(line);
+    };
 
-    // Directory argument
-    // TODO: Figure out why force_install_dir wipes libraryfolders.vdf
-    //let mut install_dir_arg: String = String::from("+force_install_dir ");
-    //install_dir_arg.push_str(&install_dir.to_string_lossy());
+    let login: SteamLogin = get_steam_login(&steam_settings);
+    let output: SteamCmdOutput = match steamcmd_session::run_command(
+        &login.username,
+        target,
+        &commands.join(" "),
+        login.answers,
+        &mut on_spawn,
+        &mut on_line,
+    ) {
+        // Commands given to a SteamCMD of its own are visible to every local user
+        Err(e) if e.is::<SessionUnavailable>() && commands.iter().any(|c| c == steamcmd::BETA_PASSWORD_FLAG) => {
+            bail!("steam_client::run_steamcmd() | Err: {e} Branch passwords are only given to a SteamCMD session!")
+        }
+        Err(e) if e.is::<SessionUnavailable>() => {
+            warn!("steam_client::run_steamcmd() {e} Running SteamCMD on its own...");
+            let (args, answers) =
+                prepare_run(get_steam_login(&steam_settings), target, commands);
+            steamcmd::run_headless(&args, answers, &mut on_spawn, &mut on_line)
+        }
+        result => result,
+    }
+    .with_context(|| "steam_client::run_steamcmd() -> ")?;
+    steamcmd::check_output(&output)?;
+    Ok(())
+}
 
-    // Login argument
-    let login_arg =
-        get_steamcmd_login(&steam_settings).with_context(|| "steam_client::download_game() -> ")?;
+/// Returns a game installed through SteamCMD as a MonarchGame.
+/// Builds for another platform are played through the Proton chosen when installing them.
+pub async fn get_installed_game(id: &str, options: &InstallOptions) -> Result<MonarchGame> {
+    let mut games: Vec<MonarchGame> = parse_steam_ids(&[String::from(id)], false, true).await;
+    if games.is_empty() {
+        bail!("steam_client::get_installed_game() | Err: Failed to find info about game: {id}")
+    }
 
-    // App ID argument
-    let mut download_arg = String::from("+app_update ");
-    download_arg.push_str(id);
-    download_arg.push_str(" validate");
+    let mut monarchgame: MonarchGame = games.remove(0);
+    monarchgame.platform = "steamcmd".to_string();
+    monarchgame.install_dir = options.install_dir.clone();
+    monarchgame.platform_override = options.platform_override.clone();
+    monarchgame.compatibility = options.compatibility.clone();
+    monarchgame.beta_branch = options.branch.clone();
+    Ok(monarchgame)
+}
 
-    // Build the command as a string with arguments in order
-    let command: Vec<&str> = vec![
-        "+@ShutdownOnFailedCommand 1",
-        &login_arg,
-        &download_arg,
-        "+quit",
+/// Downloads the depot and manifest to pin from options, and copies it over the installed game.
+/// Blocks until SteamCMD is done.
+pub fn download_depot(
+    handle: &AppHandle,
+    id: &str,
+    options: &InstallOptions,
+    on_spawn: impl FnMut(Box<dyn ChildKiller + Send + Sync>),
+) -> Result<()> {
+    let commands: Vec<String> = vec![
+        String::from("download_depot"),
+        id.to_string(),
+        options.depot_id.clone(),
+        options.manifest_id.clone(),
     ];
 
-    // TODO: Wait for Steamcmd to return
-    // TODO: steam::steamcmd_command() should wait for SteamCMD to finish
-    steam::steamcmd_command(handle, command)
-        .await
-        .with_context(|| "steam_client::download_game() -> ")?;
+    // download_depot ignores force_install_dir and always downloads into SteamCMD's own folder
+    run_steamcmd(handle, &InstallTarget::default(), commands, on_spawn, |_| {})
+        .with_context(|| "steam_client::download_depot() -> ")?;
+    let downloaded: PathBuf = get_steamcmd_dir()
+        .join("steamapps")
+        .join("content")
+        .join(format!("app_{id}"))
+        .join(format!("depot_{}", options.depot_id));
+
+    let game_dir: PathBuf = if options.install_dir.is_empty() {
+        let info: AppInfo =
+            get_app_info(handle, id).with_context(|| "steam_client::download_depot() -> ")?;
+        steamcmd_game_dir(&info)
+    } else {
+        PathBuf::from(&options.install_dir)
+    };
 
-    let mut monarchgame: MonarchGame =
-        parse_steam_ids(&[String::from(id)], false, true).await[0].clone();
-    monarchgame.platform = "steamcmd".to_string();
-    Ok(monarchgame)
+    info!("Copying {} to {}", downloaded.display(), game_dir.display());
+    copy_dir(&downloaded, &game_dir).with_context(|| "steam_client::download_depot() -> ")?;
+    if let Err(e) = fs::remove_dir_all(&downloaded) {
+        warn!("steam_client::download_depot() Failed to remove: {} | Err: {e}", downloaded.display());
+    }
+    Ok(())
 }
 
-/// Uninstall a Steam game via SteamCMD
-pub async fn uninstall_game(handle: &AppHandle, id: &str) -> Result<()> {
-    let steam_settings = get_settings_state().steam;
-    if !steam_settings.manage {
-        warn!("steam_client::uninstall_game() User tried to uninstall game without allowing Monarch to manage Steam! Cancelling uninstall...");
-        bail!("steam_client::download_game() | Err: Not allowed to manage games. Check settings.")
+/// Saves the password of a Steam branch in the keyring, to be used whenever it's installed.
+pub fn set_branch_password(id: &str, branch: &str, password: &str) -> Result<()> {
+    set_credentials("steam-beta", &format!("{id}:{branch}"), password)
+        .with_context(|| "steam_client::set_branch_password() -> ")
+}
+
+fn get_branch_password(id: &str, branch: &str) -> Option<String> {
+    if branch == "public" {
+        return None;
     }
+    get_password("steam-beta", &format!("{id}:{branch}"))
+        .ok()
+        .filter(|password| !password.is_empty())
+}
 
-    let login_arg = get_steamcmd_login(&steam_settings)?;
-    let remove_arg: String = format!("+app_uninstall {id}");
-    let command: Vec<&str> = vec![
-        "+@ShutdownOnFailedCommand 1",
-        &login_arg,
-        &remove_arg,
-        "+quit",
-    ];
+/// Returns the app info of a game, from the cache if it's there.
+/// Blocks while SteamCMD runs if it isn't.
+pub fn get_app_info(handle: &AppHandle, id: &str) -> Result<AppInfo> {
+    match steam_appinfo::read_cache(id) {
+        Some(info) => Ok(info),
+        None => fetch_app_info(handle, id).with_context(|| "steam_client::get_app_info() -> "),
+    }
+}
 
-    steam::steamcmd_command(handle, command)
-        .await
-        .with_context(|| "steam_client::uninstall_game() -> ")
+/// Gets the app info of a game from SteamCMD and caches it. Blocks until SteamCMD is done.
+pub fn fetch_app_info(handle: &AppHandle, id: &str) -> Result<AppInfo> {
+    let mut result: Result<AppInfo> = print_app_info(handle, id);
+
+    // Right after logging in SteamCMD can print the app info before it has been received
+    if result.is_err() {
+        warn!("steam_client::fetch_app_info() No app info printed for: {id}, trying again...");
+        result = print_app_info(handle, id);
+    }
+
+    let info: AppInfo = result.with_context(|| "steam_client::fetch_app_info() -> ")?;
+    steam_appinfo::write_cache(&info).with_context(|| "steam_client::fetch_app_info() -> ")?;
+    Ok(info)
 }
 
-/// Uninstall a Steam game via SteamCMD
-pub async fn update_game(handle: &AppHandle, id: &str) -> Result<()> {
-    let steam_settings = get_settings_state().steam;
-    if !steam_settings.manage {
-        warn!("steam_client::uninstall_game() User tried to uninstall game without allowing Monarch to manage Steam! Cancelling uninstall...");
-        bail!("steam_client::download_game() | Err: Not allowed to manage games. Check settings.")
+fn print_app_info(handle: &AppHandle, id: &str) -> Result<AppInfo> {
+    // SteamCMD prints the app info it has cached, which a long running session keeps around
+    let update: Vec<String> = vec![String::from("app_info_update"), String::from("1")];
+    run_steamcmd(handle, &InstallTarget::default(), update, |_| {}, |_| {})
+        .with_context(|| "steam_client::print_app_info() -> ")?;
+
+    let commands: Vec<String> = vec![String::from("app_info_print"), id.to_string()];
+
+    // Output kept by SteamCMD runs is limited, while app info can be thousands of lines long
+    let mut lines: Vec<String> = Vec::new();
+    run_steamcmd(handle, &InstallTarget::default(), commands, |_| {}, |line| {
+        lines.push(line.to_string())
+    })
+    .with_context(|| "steam_client::print_app_info() -> ")?;
+
+    AppInfo::parse(id, &lines).with_context(|| "steam_client::print_app_info() -> ")
+}
+
+/// Returns a game installed through SteamCMD set up to be launched straight from disk, with
+/// the executable, arguments and working folder of its app info's launch config.
+/// Builds for another platform keep the game's Proton as their runner.
+pub fn resolve_launch(game: &MonarchGame, info: &AppInfo) -> Result<MonarchGame> {
+    let platform: &str = if game.platform_override.is_empty() {
+        steamcmd::NATIVE_PLATFORM
+    } else {
+        &game.platform_override
+    };
+    let Some(config) = info.launch_config(platform) else {
+        bail!("steam_client::resolve_launch() | Err: {} has no launch config for: {platform}", game.name)
+    };
+    if platform != steamcmd::NATIVE_PLATFORM && game.compatibility.is_empty() {
+        bail!("steam_client::resolve_launch() | Err: No Proton set to play the {platform} build of {} with!", game.name)
     }
 
-    let login_arg = get_steamcmd_login(&steam_settings)?;
-    let update_arg: String = format!("+app_update {id} validate");
-    let command: Vec<&str> = vec![
-        "+@ShutdownOnFailedCommand 1",
-        &login_arg,
-        &update_arg,
-        "+quit",
-    ];
+    // Games installed before Monarch chose their folder are in SteamCMD's own
+    let install_dir: PathBuf = if game.install_dir.is_empty() {
+        steamcmd_game_dir(info)
+    } else {
+        PathBuf::from(&game.install_dir)
+    };
 
-    steam::steamcmd_command(handle, command)
+    let mut resolved: MonarchGame = game.clone();
+    resolved.executable_path = install_dir
+        .join(relative_path(&config.executable))
+        .display()
+        .to_string();
+    if resolved.working_dir.is_empty() && !config.working_dir.is_empty() {
+        resolved.working_dir = install_dir
+            .join(relative_path(&config.working_dir))
+            .display()
+            .to_string();
+    }
+    resolved.launch_args = [config.arguments.as_str(), game.launch_args.as_str()]
+        .into_iter()
+        .filter(|args| !args.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ");
+    Ok(resolved)
+}
+
+/// Returns the folder a game is installed to in SteamCMD's own folder.
+fn steamcmd_game_dir(info: &AppInfo) -> PathBuf {
+    get_steamcmd_dir()
+        .join("steamapps")
+        .join("common")
+        .join(&info.install_dir)
+}
+
+/// Returns if a newer build is out for a game installed through SteamCMD, comparing the build
+/// in its appmanifest against the one of the branch it's on. Refreshes the game's cached app
+/// info. Blocks until SteamCMD is done.
+pub fn is_update_available(handle: &AppHandle, game: &MonarchGame) -> Result<bool> {
+    let info: AppInfo = fetch_app_info(handle, &game.platform_id)
+        .with_context(|| "steam_client::is_update_available() -> ")?;
+    let branch: &str = if game.beta_branch.is_empty() {
+        "public"
+    } else {
+        &game.beta_branch
+    };
+    let Some(latest) = info
+        .branch(branch)
+        .map(|branch| branch.build_id.as_str())
+        .filter(|build_id| !build_id.is_empty())
+    else {
+        bail!("steam_client::is_update_available() | Err: No build id of branch {branch} in app info of: {}", game.platform_id)
+    };
+
+    let installed: String =
+        installed_build_id(game).with_context(|| "steam_client::is_update_available() -> ")?;
+    Ok(installed != latest)
+}
+
+/// Returns the build id SteamCMD wrote to a game's appmanifest when installing it.
+fn installed_build_id(game: &MonarchGame) -> Result<String> {
+    // SteamCMD keeps appmanifests in steamapps of the folder it installed to
+    let library_dir: PathBuf = if game.install_dir.is_empty() {
+        get_steamcmd_dir()
+    } else {
+        PathBuf::from(&game.install_dir)
+    };
+    let path: PathBuf = library_dir
+        .join("steamapps")
+        .join(format!("appmanifest_{}.acf", game.platform_id));
+
+    let content: String = fs::read_to_string(&path).with_context(|| {
+        format!(
+            "steam_client::installed_build_id() Error reading: {file} | Err: ",
+            file = path.display()
+        )
+    })?;
+    let manifest: KeyValues =
+        parse_keyvalues(&content).with_context(|| "steam_client::installed_build_id() -> ")?;
+
+    match manifest
+        .get("AppState")
+        .and_then(|state| state.get_str("buildid"))
+    {
+        Some(build_id) => Ok(build_id.to_string()),
+        None => bail!("steam_client::installed_build_id() | Err: No build id in: {}", path.display()),
+    }
+}
+
+/// Turns a path from app info, which uses backslashes for Windows builds, into a path.
+fn relative_path(path: &str) -> PathBuf {
+    path.split(['\\', '/'])
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+/// Uninstall a Steam game via SteamCMD, from the folder it was installed to if known
+pub async fn uninstall_game(handle: &AppHandle, id: &str, install_dir: Option<String>) -> Result<()> {
+    let commands: Vec<String> = vec![String::from("app_uninstall"), id.to_string()];
+
+    let handle: AppHandle = handle.clone();
+    task::spawn_blocking(move || {
+        let target: InstallTarget = InstallTarget {
+            install_dir,
+            platform: None,
+        };
+        run_steamcmd(&handle, &target, commands, |_| {}, |_| {})
+    })
         .await
-        .with_context(|| "steam_client::update_game() -> ")
+        .with_context(|| "steam_client::uninstall_game() SteamCMD task panicked! | Err: ")?
+        .with_context(|| "steam_client::uninstall_game() -> ")
+}
+
+/// Returns the folder a game is installed to: a folder named after the game in the chosen
+/// game folder, which has to be Monarch's game folder or one of the Steam game folders.
+/// Monarch's game folder is used if none is chosen.
+pub fn get_install_dir(folder: Option<&str>, name: &str, id: &str) -> Result<PathBuf> {
+    let settings = get_settings_state();
+    let folder: String = match folder {
+        None => settings.monarch.game_folder,
+        Some(folder)
+            if folder == settings.monarch.game_folder
+                || settings.steam.game_folders.iter().any(|f| f == folder) =>
+        {
+            folder.to_string()
+        }
+        Some(folder) => {
+            bail!("steam_client::get_install_dir() | Err: {folder} is not one of the game folders in settings!")
+        }
+    };
+
+    // Game names can contain characters folder names can't
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_alphanumeric() || " -_.'()".contains(*c))
+        .collect::<String>();
+    let name: &str = name.trim_matches(|c: char| c == '.' || c.is_whitespace());
+
+    Ok(PathBuf::from(folder).join(if name.is_empty() { id } else { name }))
 }
 
 /// Returns path to Monarchs installed version of SteamCMD
@@ -208,49 +512,93 @@ pub async fn parse_steam_ids(
     games
 }
 
-/// Since login is used for multiple commands it gets
-/// abstracted to it's own function.
-fn get_steamcmd_login(steam_settings: &LauncherSettings) -> Result<String> {
+/// Login argument for SteamCMD shown in the terminal. SteamCMD uses its cached login if there
+/// is one, otherwise the user is prompted for their password in the terminal.
+fn get_steamcmd_login(steam_settings: &LauncherSettings) -> String {
+    format!("+login {}", quote_shell_word(&steam_settings.username))
+}
+
+/// Everything needed to log in to SteamCMD in the background.
+struct SteamLogin {
+    username: String,
+    answers: LoginAnswers,
+}
+
+/// Reads the Steam login from settings and the keyring.
+fn get_steam_login(steam_settings: &LauncherSettings) -> SteamLogin {
     let username: &str = &steam_settings.username;
-    let password: String = match get_password("steam", &username) {
-        Ok(p) => p,
+    let password: Option<String> = match get_password("steam", username) {
+        Ok(p) if !p.is_empty() => Some(p),
+        Ok(_) => None,
         Err(e) => {
-            warn!("steam_client::get_steamcmd_login() Failed to get password for {username}! | Err: {e}");
-            info!("SteamCMD will prompt for password.");
-            String::from("")
+            warn!("steam_client::get_steam_login() Failed to get password for {username}! | Err: {e}");
+            info!("SteamCMD will have to use its cached login.");
+            None
         }
     };
 
-    // Login argument
-    let mut login_arg = String::from("+login ");
-    login_arg.push_str(username);
+    SteamLogin {
+        username: username.to_string(),
+        answers: LoginAnswers {
+            password,
+            two_factor_code: get_two_factor_code(steam_settings),
+        },
+    }
+}
 
-    if !password.is_empty() {
-        login_arg.push(' ');
-        login_arg.push_str(&password);
+/// Generating codes from a stored secret defeats the point of 2FA, so it's only done
+/// if the user has chosen to store their secret. Otherwise Steam Guard is prompted for.
+fn get_two_factor_code(steam_settings: &LauncherSettings) -> Option<String> {
+    if !steam_settings.twofa {
+        return None;
     }
 
-    // Current solution is to store the secret in keystore, which essentially
-    // disables the point of 2fa, at least on computers with Monarch.
-    // TODO: Look into other possible solutions for Steamgaurd.
-    match get_password("steam-secret", username) {
+    match get_password("steam-secret", &steam_settings.username) {
         Ok(secret) => {
             if !secret.is_empty() {
                 info!("Steam TOTP detected in Monarch!");
-                let totp = generate(&secret).unwrap();
-                login_arg.push(' ');
-                login_arg.push_str(&totp);
-            } else {
-                warn!("Steam TOTP was found! However the string was empty.");
+                return generate(&secret).ok();
             }
+            warn!("Steam TOTP was found! However the string was empty.");
         }
         Err(e) => {
-            error!("steam_client::get_steamcmd_login() Did not find steam secret. | Err: {e}");
-            warn!("No Steam TOTP detected! Might require mobile 2fa.");
+            error!("steam_client::get_two_factor_code() Did not find steam secret. | Err: {e}");
+            warn!("No Steam TOTP detected! Steam Guard will be prompted for.");
         }
     }
+    None
+}
+
+/// Splits a login into the arguments SteamCMD is started with and the answers to its prompts.
+/// Arguments are visible to every local user, so they only ever contain the username.
+fn prepare_run(
+    login: SteamLogin,
+    target: &InstallTarget,
+    commands: Vec<String>,
+) -> (Vec<String>, LoginAnswers) {
+    // SteamCMD has to be allowed to prompt, both for the password and for Steam Guard
+    let mut args: Vec<String> = vec![String::from("+@ShutdownOnFailedCommand"), String::from("1")];
+
+    // Only taken before logging in
+    if let Some(install_dir) = &target.install_dir {
+        args.push(String::from("+force_install_dir"));
+        args.push(install_dir.clone());
+    }
+    if let Some(platform) = target.forced_platform() {
+        args.push(String::from("+@sSteamCmdForcePlatformType"));
+        args.push(platform.to_string());
+    }
+    args.push(String::from("+login"));
+    args.push(login.username);
+
+    // Commands are given as arguments by starting them with +
+    if let Some((command, command_args)) = commands.split_first() {
+        args.push(format!("+{command}"));
+        args.extend_from_slice(command_args);
+    }
+    args.push(String::from("+quit"));
 
-    Ok(login_arg)
+    (args, login.answers)
 }
 
 /// Helper function to parse individual steam ids. Allows for concurrent parsing.
@@ -296,6 +644,7 @@ async fn parse_id_monarch_com(id: String, is_cache: bool) -> Result<MonarchGame>
         let mut monarch_game = MonarchGame::from(&game_info);
         monarch_game.thumbnail_path = thumbnail_path;
         monarch_game.download_thumbnail(game_info.cover_url).await;
+        monarch_game.download_artwork(game_info.artwork_url).await;
         return Ok(monarch_game);
     }
 
@@ -375,6 +724,8 @@ async fn parse_id_steampowered_com(id: String, is_cache: bool) -> Result<Monarch
     let store_url = format!("https://store.steampowered.com/app/{id}");
     let cover_url: String =
         format!("https://steamcdn-a.akamaihd.net/steam/apps/{id}/library_600x900_2x.jpg");
+    let artwork_url: String =
+        format!("https://steamcdn-a.akamaihd.net/steam/apps/{id}/library_hero.jpg");
 
     // Parse content into MonarchGame
     let thumbnail_path = if is_cache {
@@ -382,7 +733,118 @@ async fn parse_id_steampowered_com(id: String, is_cache: bool) -> Result<Monarch
     } else {
         String::from(generate_library_image_path(&name).to_str().unwrap())
     };
-    let monarch_game = MonarchGame::new(&name, -1, "steam", &id, &store_url, "", &thumbnail_path);
+    let mut monarch_game =
+        MonarchGame::new(&name, -1, "steam", &id, &store_url, "", &thumbnail_path);
+
+    let data: &Value = &game_json[&id]["data"];
+    monarch_game.summary = data["short_description"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    monarch_game.developer = join_json_strings(&data["developers"]);
+    monarch_game.publisher = join_json_strings(&data["publishers"]);
+    monarch_game.release_date = data["release_date"]["date"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    monarch_game.genres = data["genres"]
+        .as_array()
+        .map(|genres| {
+            genres
+                .iter()
+                .filter_map(|genre| genre["description"].as_str())
+                .map(|genre| genre.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
     monarch_game.download_thumbnail(cover_url).await;
+    monarch_game.download_artwork(artwork_url).await;
     Ok(monarch_game)
 }
+
+/// Joins a json array of strings, such as Steams list of developers, into one string.
+fn join_json_strings(value: &Value) -> String {
+    value
+        .as_array()
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(|v| v.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ")
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monarch_games::steamcmd::SteamCmdError;
+    use portable_pty::ExitStatus;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn steamcmd_args_never_contain_secrets() {
+        let login: SteamLogin = SteamLogin {
+            username: String::from("monarch"),
+            answers: LoginAnswers {
+                password: Some(String::from("hunter2")),
+                two_factor_code: Some(String::from("X7K2Q")),
+            },
+        };
+        let commands: Vec<String> = vec![String::from("app_update"), String::from("440")];
+
+        let target: InstallTarget = InstallTarget {
+            install_dir: Some(String::from("/games/Team Fortress 2")),
+            platform: None,
+        };
+
+        let (args, answers) = prepare_run(login, &target, commands);
+
+        for secret in ["hunter2", "X7K2Q"] {
+            assert!(
+                args.iter().all(|arg| !arg.contains(secret)),
+                "Secret found in SteamCMD arguments: {args:?}"
+            );
+        }
+        assert!(args.contains(&String::from("monarch")));
+        assert_eq!(answers.password.as_deref(), Some("hunter2"));
+        assert_eq!(answers.two_factor_code.as_deref(), Some("X7K2Q"));
+    }
+
+    #[test]
+    fn collected_output_never_contains_branch_password() {
+        let command: &str = "app_update 440 -beta prerelease -betapassword hunter2 validate";
+        // The session's pty echoes the command after the console prompt
+        let output: [String; 3] = [
+            format!("Steam>{command}"),
+            String::from(" Update state (0x3) reconfiguring, progress: 0.00 (0 / 0)"),
+            command.to_string(),
+        ];
+
+        let mut seen: Vec<String> = Vec::new();
+        let mut lines: VecDeque<String> = VecDeque::new();
+        for line in &output {
+            steamcmd_session::keep_line(&mut lines, line, &mut |line| seen.push(line.to_string()));
+        }
+        let error: SteamCmdError = steamcmd::check_output(&SteamCmdOutput {
+            status: ExitStatus::with_exit_code(8),
+            lines: lines.clone().into(),
+        })
+        .unwrap_err();
+
+        let collected: Vec<String> = seen
+            .into_iter()
+            .chain(lines)
+            .chain([error.to_string(), steamcmd::redact(command)])
+            .collect();
+        for line in &collected {
+            assert!(!line.contains("hunter2"), "Branch password found in output: {line}");
+        }
+        assert_eq!(
+            steamcmd::redact(command),
+            "app_update 440 -beta prerelease -betapassword *** validate"
+        );
+    }
+}
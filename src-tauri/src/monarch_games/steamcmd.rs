@@ -0,0 +1,545 @@
+/*
+* Running SteamCMD in the background and reading its output.
+*
+* SteamCMD only prints progress line by line when attached to a terminal, so it's run in a pty
+* that is never shown to the user. Every line of output is passed on as it's printed.
+*
+* SteamCMD often exits successfully even when a command has failed, so its output is checked
+* for known failures and turned into a SteamCmdError.
+*
+* Passwords are never passed as arguments, which every local user can read. SteamCMD is told
+* only the username and prompts for the rest, which is answered by writing to the pty.
+* When SteamCMD asks for a Steam Guard code Monarch can't answer, the prompt is passed on like
+* any other line and the code is written once the user has submitted it via
+* submit_steam_guard_code.
+*/
+
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use portable_pty::{
+    native_pty_system, Child, ChildKiller, CommandBuilder, ExitStatus, MasterPty, PtySize,
+};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing::{info, warn};
+
+#[cfg(target_os = "windows")]
+use super::windows::steam;
+
+#[cfg(target_os = "macos")]
+use super::macos::steam;
+
+#[cfg(target_os = "linux")]
+use super::linux::steam;
+
+/// Lines of output kept from a run.
+pub const KEPT_LINES: usize = 500;
+
+/// Progress lines look like: Update state (0x61) downloading, progress: 45.67 (123456 / 789012)
+static PROGRESS_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"Update state \(0x[0-9a-fA-F]+\) ([^,]+), progress: ([\d.]+) \((\d+) / (\d+)\)")
+        .unwrap()
+});
+
+/// Prompt of SteamCMD's console, shown when it's waiting for the next command.
+const CONSOLE_PROMPT: &str = "steam>";
+
+/// Prompts SteamCMD waits at for input, lowercase.
+const PROMPTS: [&str; 4] = ["password:", "two-factor code:", "steam guard code:", CONSOLE_PROMPT];
+
+/// Flag of app_update the password of a branch follows, which must never show in output.
+pub const BETA_PASSWORD_FLAG: &str = "-betapassword";
+
+/// Platforms SteamCMD can be made to download builds for, instead of the one Monarch runs on.
+pub const PLATFORMS: [&str; 3] = ["windows", "macos", "linux"];
+
+#[cfg(target_os = "windows")]
+pub const NATIVE_PLATFORM: &str = "windows";
+
+#[cfg(target_os = "macos")]
+pub const NATIVE_PLATFORM: &str = "macos";
+
+#[cfg(target_os = "linux")]
+pub const NATIVE_PLATFORM: &str = "linux";
+
+/// Input of a SteamCMD run, shared with submit_steam_guard_code while it waits for a code.
+pub type PtyInput = Arc<Mutex<Box<dyn Write + Send>>>;
+
+/// Input of the SteamCMD run currently waiting for a Steam Guard code.
+static WAITING_FOR_CODE: Lazy<Mutex<Option<PtyInput>>> = Lazy::new(|| Mutex::new(None));
+
+/// Where SteamCMD installs, and looks for, a game and which platform's build it downloads.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InstallTarget {
+    pub install_dir: Option<String>, // None for SteamCMD's own folder
+    pub platform: Option<String>,    // None for the platform Monarch runs on
+}
+
+impl InstallTarget {
+    /// Platform SteamCMD has to be forced to, None if it's the one Monarch runs on.
+    pub fn forced_platform(&self) -> Option<&str> {
+        self.platform
+            .as_deref()
+            .filter(|platform| *platform != NATIVE_PLATFORM)
+    }
+}
+
+/// Answers to the prompts SteamCMD stops at while logging in, each written once at most.
+#[derive(Default)]
+pub struct LoginAnswers {
+    pub password: Option<String>,
+    pub two_factor_code: Option<String>, // Generated from a stored secret, if the user opted in
+}
+
+impl LoginAnswers {
+    /// Takes the answer to a prompt, None if there is none or it has already been given.
+    fn take(&mut self, prompt: &str) -> Option<String> {
+        let prompt: String = prompt.to_lowercase();
+        if prompt.ends_with("password:") {
+            self.password.take()
+        } else if prompt.ends_with("two-factor code:") {
+            self.two_factor_code.take()
+        } else {
+            None
+        }
+    }
+}
+
+/// Ways Steam asks for a Steam Guard code, sent to the frontend in a steam-guard-required event.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SteamGuardPrompt {
+    TwoFactor, // Code from the Steam Mobile app
+    Email,     // Code sent by email
+    Mobile,    // Login has to be confirmed in the Steam Mobile app, no code is needed
+}
+
+/// Known ways SteamCMD fails, with messages meant to be shown to the user.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub enum SteamCmdError {
+    InvalidPassword,
+    PasswordRequired,
+    SteamGuardRequired,
+    SteamGuardMismatch,
+    SteamGuardTimeout,
+    RateLimitExceeded,
+    NoSubscription,
+    NotEnoughDiskSpace,
+    DiskWriteFailure,
+    MissingConfiguration,
+    InvalidPlatform,
+    NoConnection,
+    Unknown { exit_code: u32, last_line: String },
+}
+
+impl fmt::Display for SteamCmdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPassword => write!(f, "Steam says your password is wrong. Check your Steam login in settings."),
+            Self::PasswordRequired => write!(f, "SteamCMD needs your password. Log in to Steam in settings."),
+            Self::SteamGuardRequired => write!(f, "Steam Guard needs a code to log in."),
+            Self::SteamGuardMismatch => write!(f, "The Steam Guard code was wrong or has expired."),
+            Self::SteamGuardTimeout => write!(f, "No Steam Guard code was entered in time. Try again and enter the code when asked."),
+            Self::RateLimitExceeded => write!(f, "Steam has received too many login attempts. Wait a while before trying again."),
+            Self::NoSubscription => write!(f, "You don't own this game on Steam."),
+            Self::NotEnoughDiskSpace => write!(f, "There isn't enough disk space to install this game."),
+            Self::DiskWriteFailure => write!(f, "Steam couldn't write the game to disk. Check that the game folder is writable."),
+            Self::MissingConfiguration => write!(f, "Steam has no download for this game on your platform."),
+            Self::InvalidPlatform => write!(f, "This game isn't available for your platform."),
+            Self::NoConnection => write!(f, "Could not connect to Steam. Check your internet connection."),
+            Self::Unknown { exit_code, last_line } => {
+                write!(f, "SteamCMD failed with exit code {exit_code}: {last_line}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SteamCmdError {}
+
+/// Text SteamCMD prints for each failure, matched case-insensitively.
+const KNOWN_FAILURES: [(&str, SteamCmdError); 14] = [
+    ("invalid password", SteamCmdError::InvalidPassword),
+    ("cached credentials not found", SteamCmdError::PasswordRequired),
+    ("password required", SteamCmdError::PasswordRequired),
+    ("two-factor code mismatch", SteamCmdError::SteamGuardMismatch),
+    ("invalid login auth code", SteamCmdError::SteamGuardMismatch),
+    ("account logon denied", SteamCmdError::SteamGuardRequired),
+    ("rate limit exceeded", SteamCmdError::RateLimitExceeded),
+    ("no subscription", SteamCmdError::NoSubscription),
+    ("not enough disk space", SteamCmdError::NotEnoughDiskSpace),
+    ("state is 0x202", SteamCmdError::NotEnoughDiskSpace),
+    ("disk write failure", SteamCmdError::DiskWriteFailure),
+    ("missing configuration", SteamCmdError::MissingConfiguration),
+    ("invalid platform", SteamCmdError::InvalidPlatform),
+    ("no connection", SteamCmdError::NoConnection),
+];
+
+/// Output of a SteamCMD run that has finished.
+pub struct SteamCmdOutput {
+    pub status: ExitStatus,
+    pub lines: Vec<String>, // Last lines printed
+}
+
+/// Progress of an app update as printed by SteamCMD.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct UpdateProgress {
+    pub state: String, // Such as downloading, verifying or preallocating
+    pub progress: f64, // Percent
+    pub current: u64,  // Bytes
+    pub total: u64,
+}
+
+/// SteamCMD started in a pty that is never shown.
+pub struct SteamCmdProcess {
+    pub child: Box<dyn Child + Send + Sync>,
+    pub reader: Box<dyn Read + Send>,
+    pub input: PtyInput,
+    _master: Box<dyn MasterPty + Send>, // SteamCMD is hung up on once this is dropped
+}
+
+/// Output of SteamCMD, split into lines and prompts waiting for input.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Output {
+    Line(String),
+    Prompt(String),
+}
+
+/// Splits output read from the pty into lines and prompts.
+#[derive(Default)]
+pub struct OutputSplitter {
+    pending: String,
+}
+
+impl OutputSplitter {
+    /// Returns the lines and prompts completed by output just read.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Output> {
+        self.pending.push_str(&String::from_utf8_lossy(bytes));
+        let mut output: Vec<Output> = Vec::new();
+
+        // Progress is sometimes rewritten in place with \r instead of printed on a new line
+        while let Some(end) = self.pending.find(['\n', '\r']) {
+            let line: String = self.pending[..end].trim().to_string();
+            self.pending.drain(..=end);
+
+            if !line.is_empty() {
+                output.push(Output::Line(line));
+            }
+        }
+
+        // Prompts wait for input at the end of a line that is never finished
+        if is_prompt(&self.pending) {
+            output.push(Output::Prompt(self.pending.trim().to_string()));
+            self.pending.clear();
+        }
+        output
+    }
+
+    /// Returns output left after SteamCMD has exited, if any.
+    pub fn finish(&mut self) -> Option<String> {
+        let rest: String = self.pending.trim().to_string();
+        self.pending.clear();
+        (!rest.is_empty()).then_some(rest)
+    }
+}
+
+/// Starts SteamCMD with arguments in a hidden pty.
+pub fn spawn(args: &[String]) -> Result<SteamCmdProcess> {
+    let pair = native_pty_system()
+        .openpty(PtySize {
+            rows: 24,
+            cols: 200, // Wide enough for progress lines not to wrap
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+        .with_context(|| "steamcmd::spawn() Failed to open pty! | Err: ")?;
+
+    let mut cmd: CommandBuilder = CommandBuilder::new(steam::steamcmd_executable());
+    cmd.args(args);
+
+    let child = pair
+        .slave
+        .spawn_command(cmd)
+        .with_context(|| "steamcmd::spawn() Failed to start SteamCMD! | Err: ")?;
+
+    // The reader only reaches the end once every handle to the other side is closed
+    drop(pair.slave);
+    let reader = pair
+        .master
+        .try_clone_reader()
+        .with_context(|| "steamcmd::spawn() Failed to read from pty! | Err: ")?;
+    let input: PtyInput = Arc::new(Mutex::new(
+        pair.master
+            .take_writer()
+            .with_context(|| "steamcmd::spawn() Failed to write to pty! | Err: ")?,
+    ));
+
+    Ok(SteamCmdProcess {
+        child,
+        reader,
+        input,
+        _master: pair.master,
+    })
+}
+
+/// Runs SteamCMD with arguments without showing it, calling on_line for every line it prints,
+/// including prompts waiting for input. Login prompts are answered from answers.
+/// on_spawn gets a handle that can be used to kill SteamCMD while it runs.
+/// Blocks until SteamCMD exits.
+pub fn run_headless(
+    args: &[String],
+    mut answers: LoginAnswers,
+    mut on_spawn: impl FnMut(Box<dyn ChildKiller + Send + Sync>),
+    mut on_line: impl FnMut(&str),
+) -> Result<SteamCmdOutput> {
+    info!("Running SteamCMD in the background...");
+    let mut steamcmd: SteamCmdProcess = spawn(args).with_context(|| "steamcmd::run_headless() -> ")?;
+    on_spawn(steamcmd.child.clone_killer());
+
+    let mut lines: VecDeque<String> = VecDeque::new();
+    let mut splitter: OutputSplitter = OutputSplitter::default();
+    let mut buffer: [u8; 4096] = [0; 4096];
+
+    // Reading fails instead of ending on some platforms once SteamCMD has exited
+    while let Ok(read) = steamcmd.reader.read(&mut buffer) {
+        if read == 0 {
+            break;
+        }
+
+        for output in splitter.push(&buffer[..read]) {
+            let line: String = match output {
+                Output::Line(line) => line,
+                Output::Prompt(prompt) => {
+                    if let Err(e) = answer_prompt(&prompt, &mut answers, &steamcmd.input) {
+                        if let Err(e) = steamcmd.child.kill() {
+                            warn!("steamcmd::run_headless() Failed to stop SteamCMD! | Err: {e}");
+                        }
+                        let _ = steamcmd.child.wait();
+                        stop_waiting(&steamcmd.input);
+                        return Err(e.context("steamcmd::run_headless() -> "));
+                    }
+                    prompt
+                }
+            };
+
+            on_line(&line);
+            if lines.len() == KEPT_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line);
+        }
+    }
+    if let Some(rest) = splitter.finish() {
+        on_line(&rest);
+        lines.push_back(rest);
+    }
+
+    let status: ExitStatus = steamcmd
+        .child
+        .wait()
+        .with_context(|| "steamcmd::run_headless() Failed to wait for SteamCMD! | Err: ")?;
+    info!("SteamCMD exited with status: {:?}", status);
+
+    stop_waiting(&steamcmd.input);
+
+    Ok(SteamCmdOutput {
+        status,
+        lines: lines.into(),
+    })
+}
+
+/// Answers a prompt SteamCMD is waiting at, from answers if possible. Steam Guard prompts that
+/// can't be answered wait for submit_steam_guard_code.
+/// Fails if SteamCMD is waiting for a password Monarch doesn't have.
+pub fn answer_prompt(prompt: &str, answers: &mut LoginAnswers, input: &PtyInput) -> Result<()> {
+    if is_console_prompt(prompt) {
+        return Ok(());
+    }
+
+    if let Some(answer) = answers.take(prompt) {
+        write_input(input, &answer).with_context(|| "steamcmd::answer_prompt() -> ")
+    } else if parse_guard_prompt(prompt).is_some() {
+        *lock_waiting() = Some(input.clone());
+        Ok(())
+    } else {
+        // Asked for a password again, so the one given was wrong, or there was none
+        warn!("steamcmd::answer_prompt() SteamCMD is waiting for a password! Stopping...");
+        Err(SteamCmdError::PasswordRequired.into())
+    }
+}
+
+/// Returns if a prompt is SteamCMD's console waiting for the next command.
+pub fn is_console_prompt(prompt: &str) -> bool {
+    prompt.trim().eq_ignore_ascii_case(CONSOLE_PROMPT)
+}
+
+/// Writes a Steam Guard code to the SteamCMD run waiting for one.
+pub fn submit_steam_guard_code(code: &str) -> Result<()> {
+    let Some(input) = lock_waiting().take() else {
+        bail!("steamcmd::submit_steam_guard_code() | Err: SteamCMD isn't waiting for a Steam Guard code!")
+    };
+    write_input(&input, code.trim()).with_context(|| "steamcmd::submit_steam_guard_code() -> ")
+}
+
+/// Writes a line to a running SteamCMD.
+pub fn write_input(input: &PtyInput, answer: &str) -> Result<()> {
+    let mut input = input.lock().unwrap_or_else(|e| e.into_inner());
+    writeln!(input, "{answer}")
+        .and_then(|_| input.flush())
+        .with_context(|| "steamcmd::write_input() Failed to write to SteamCMD! | Err: ")
+}
+
+/// Masks branch passwords given with -betapassword in a command, or in SteamCMD's echo of it.
+pub fn redact(text: &str) -> String {
+    let mut redacted: String = String::with_capacity(text.len());
+    let mut rest: &str = text;
+
+    while let Some(at) = rest.find(BETA_PASSWORD_FLAG) {
+        let (before, after) = rest.split_at(at + BETA_PASSWORD_FLAG.len());
+        let password_start: usize = after.len() - after.trim_start().len();
+        let password_end: usize = after[password_start..]
+            .find(char::is_whitespace)
+            .map_or(after.len(), |end| password_start + end);
+
+        redacted.push_str(before);
+        redacted.push_str(&after[..password_start]);
+        if password_end > password_start {
+            redacted.push_str("***");
+        }
+        rest = &after[password_end..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+/// Returns how Steam asks for a Steam Guard code if a line is such a prompt.
+pub fn parse_guard_prompt(line: &str) -> Option<SteamGuardPrompt> {
+    let line: String = line.to_lowercase();
+    if line.contains("two-factor code:") {
+        Some(SteamGuardPrompt::TwoFactor)
+    } else if line.contains("steam guard code:") {
+        Some(SteamGuardPrompt::Email)
+    } else if line.contains("confirm the login in the steam mobile app") {
+        Some(SteamGuardPrompt::Mobile)
+    } else {
+        None
+    }
+}
+
+/// Returns the error a line of output reports, None if it doesn't report one.
+pub fn parse_error(line: &str) -> Option<SteamCmdError> {
+    let line: String = line.to_lowercase();
+    KNOWN_FAILURES
+        .iter()
+        .find(|(text, _)| line.contains(text))
+        .map(|(_, error)| error.clone())
+}
+
+/// Checks the output of a finished run for failures, also when SteamCMD exited successfully.
+pub fn check_output(output: &SteamCmdOutput) -> Result<(), SteamCmdError> {
+    if let Some(error) = output.lines.iter().find_map(|line| parse_error(line)) {
+        return Err(error);
+    }
+
+    if !output.status.success() {
+        // Exit codes SteamCMD is known to use when it fails without saying why
+        return Err(match output.status.exit_code() {
+            3 => SteamCmdError::NoConnection,
+            5 => SteamCmdError::InvalidPassword,
+            exit_code => SteamCmdError::Unknown {
+                exit_code,
+                last_line: output.lines.last().cloned().unwrap_or_default(),
+            },
+        });
+    }
+    Ok(())
+}
+
+/// Returns if unfinished output ends with a prompt SteamCMD waits at for input.
+fn is_prompt(pending: &str) -> bool {
+    let pending: String = pending.trim_end().to_lowercase();
+    PROMPTS.iter().any(|prompt| pending.ends_with(prompt))
+}
+
+/// Returns if the run with input is waiting for the user to submit a Steam Guard code.
+pub fn is_waiting_for_code(input: &PtyInput) -> bool {
+    lock_waiting()
+        .as_ref()
+        .is_some_and(|waiting| Arc::ptr_eq(waiting, input))
+}
+
+/// Stops waiting for a code if the run with input was the one asking for it.
+pub fn stop_waiting(input: &PtyInput) {
+    let mut waiting = lock_waiting();
+    if waiting.as_ref().is_some_and(|waiting| Arc::ptr_eq(waiting, input)) {
+        *waiting = None;
+    }
+}
+
+fn lock_waiting() -> MutexGuard<'static, Option<PtyInput>> {
+    WAITING_FOR_CODE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Parses a progress line printed by app_update, None for any other line.
+pub fn parse_progress(line: &str) -> Option<UpdateProgress> {
+    let captures = PROGRESS_REGEX.captures(line)?;
+
+    Some(UpdateProgress {
+        state: captures[1].trim().to_string(),
+        progress: captures[2].parse().ok()?,
+        current: captures[3].parse().ok()?,
+        total: captures[4].parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn parse_progress_reads_app_update_lines() {
+        let line: &str = " Update state (0x61) downloading, progress: 45.67 (123456 / 789012)";
+        assert_eq!(
+            parse_progress(line),
+            Some(UpdateProgress {
+                state: String::from("downloading"),
+                progress: 45.67,
+                current: 123456,
+                total: 789012,
+            })
+        );
+
+        let line: &str = "Update state (0x5) verifying install, progress: 0.00 (0 / 0)";
+        let progress: UpdateProgress = parse_progress(line).unwrap();
+        assert_eq!(progress.state, "verifying install");
+        assert_eq!((progress.current, progress.total), (0, 0));
+    }
+
+    #[test]
+    fn unanswered_steam_guard_prompts_wait_for_the_user() {
+        let input: PtyInput = Arc::new(Mutex::new(Box::new(io::sink())));
+        let mut answers: LoginAnswers = LoginAnswers::default();
+
+        answer_prompt("Steam Guard code:", &mut answers, &input).unwrap();
+        assert!(is_waiting_for_code(&input));
+
+        stop_waiting(&input);
+        assert!(!is_waiting_for_code(&input));
+    }
+
+    #[test]
+    fn parse_progress_ignores_other_output() {
+        for line in [
+            "Success! App '440' fully installed.",
+            "Update state (0x61) downloading, progress: 45.67",
+            "Update state (0x61) downloading, progress: . (1 / 2)",
+            "",
+        ] {
+            assert_eq!(parse_progress(line), None, "Parsed progress from: {line}");
+        }
+    }
+}
@@ -0,0 +1,533 @@
+/*
+* A SteamCMD kept running and logged in between commands.
+*
+* Starting SteamCMD and logging in for every command is slow and counts towards Steam's login
+* rate limit. Instead one SteamCMD is kept running per account, owned by a thread that takes
+* commands from a channel and runs them one at a time: a command is written to SteamCMD's
+* console and its output is collected until SteamCMD is back at its Steam> prompt.
+*
+* SteamCMD is started again if it has exited, or once if it lost its connection to Steam during
+* a command. It's shut down after IDLE_TIMEOUT without commands.
+*
+* SteamCMD only takes force_install_dir before logging in, so the session logs out and in again
+* when a command needs another install folder than the one before. The platform builds are
+* downloaded for is a console variable, @sSteamCmdForcePlatformType, set whenever it changes.
+*
+* Games are still launched through their own SteamCMD, as they need their own environment.
+*/
+
+use anyhow::{anyhow, bail, Context, Result};
+use once_cell::sync::Lazy;
+use portable_pty::{ChildKiller, ExitStatus};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read};
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use super::steamcmd::{
+    self, InstallTarget, LoginAnswers, Output, OutputSplitter, SteamCmdError, SteamCmdOutput,
+    SteamCmdProcess, KEPT_LINES, NATIVE_PLATFORM,
+};
+
+/// How long SteamCMD is kept running without commands.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// How long the user has to enter a Steam Guard code before the command fails.
+const STEAM_GUARD_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+static SESSION: Lazy<Mutex<Option<Session>>> = Lazy::new(|| Mutex::new(None));
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Returned when SteamCMD can't be started for a session, so it has to be run on its own.
+#[derive(Debug)]
+pub struct SessionUnavailable;
+
+impl fmt::Display for SessionUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Could not start a SteamCMD session!")
+    }
+}
+
+impl std::error::Error for SessionUnavailable {}
+
+/// Channel to the thread owning the session of an account.
+struct Session {
+    id: u64,
+    username: String,
+    requests: Sender<Request>,
+}
+
+/// A command for the session, with what's needed to log in if it isn't yet.
+struct Request {
+    command: String,
+    target: InstallTarget,
+    answers: LoginAnswers,
+    replies: Sender<Reply>,
+}
+
+/// Sent back while a command runs.
+enum Reply {
+    Started(Box<dyn ChildKiller + Send + Sync>), // Kills the session's SteamCMD, used to cancel
+    Line(String),
+    Done(Result<SteamCmdOutput>),
+}
+
+/// Runs a SteamCMD command, such as "app_update 440", in the session of an account, starting
+/// the session and logging in first if needed. Games are installed to, and looked for in, the
+/// target's install_dir, for the target's platform. Blocks until the command has finished.
+/// Fails with SessionUnavailable if SteamCMD couldn't be started.
+pub fn run_command(
+    username: &str,
+    target: &InstallTarget,
+    command: &str,
+    answers: LoginAnswers,
+    mut on_spawn: impl FnMut(Box<dyn ChildKiller + Send + Sync>),
+    mut on_line: impl FnMut(&str),
+) -> Result<SteamCmdOutput> {
+    let (replies, receiver) = mpsc::channel();
+    send(
+        username,
+        Request {
+            command: command.to_string(),
+            target: target.clone(),
+            answers,
+            replies,
+        },
+    );
+
+    for reply in receiver {
+        match reply {
+            Reply::Started(killer) => on_spawn(killer),
+            Reply::Line(line) => on_line(&line),
+            Reply::Done(result) => return result,
+        }
+    }
+    bail!(
+        "steamcmd_session::run_command() | Err: SteamCMD session ended before running: {}",
+        steamcmd::redact(command)
+    )
+}
+
+/// Sends a request to the session of an account, replacing the session of any other account.
+fn send(username: &str, request: Request) {
+    let mut session = lock_session();
+
+    let request: Request = match session.as_ref() {
+        Some(current) if current.username == username => match current.requests.send(request) {
+            Ok(()) => return,
+            Err(mpsc::SendError(request)) => request, // Session thread has stopped
+        },
+        _ => request,
+    };
+
+    // Dropping the old session's channel makes its thread shut it down
+    let id: u64 = NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed);
+    let (requests, receiver) = mpsc::channel();
+    let thread_username: String = username.to_string();
+    thread::spawn(move || run_session(id, thread_username, receiver));
+
+    // The receiver is alive until the thread is done with it, so this can't fail
+    let _ = requests.send(request);
+    *session = Some(Session {
+        id,
+        username: username.to_string(),
+        requests,
+    });
+}
+
+/// Runs requests until the session has been idle for IDLE_TIMEOUT or is replaced.
+fn run_session(id: u64, username: String, requests: Receiver<Request>) {
+    info!("Starting SteamCMD session for: {username}");
+    let mut console: Option<Console> = None;
+
+    loop {
+        match requests.recv_timeout(IDLE_TIMEOUT) {
+            Ok(request) => run_request(&mut console, &username, request),
+            Err(RecvTimeoutError::Timeout) => {
+                // Nothing can be sent once the session is removed, so the queue can be emptied
+                let mut session = lock_session();
+                if session.as_ref().is_some_and(|session| session.id == id) {
+                    *session = None;
+                }
+                drop(session);
+
+                while let Ok(request) = requests.try_recv() {
+                    run_request(&mut console, &username, request);
+                }
+                break;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    info!("Shutting down SteamCMD session for: {username}");
+    if let Some(console) = console {
+        console.quit();
+    }
+}
+
+/// Runs a request and sends back its output.
+fn run_request(console: &mut Option<Console>, username: &str, request: Request) {
+    let Request {
+        command,
+        target,
+        mut answers,
+        replies,
+    } = request;
+    let mut on_line = |line: &str| {
+        let _ = replies.send(Reply::Line(line.to_string()));
+    };
+
+    let mut result = try_command(
+        console,
+        username,
+        &target,
+        &command,
+        &mut answers,
+        &replies,
+        &mut on_line,
+    );
+    if is_disconnected(&result) {
+        warn!("steamcmd_session::run_request() SteamCMD lost its connection! Reconnecting...");
+        if let Some(console) = console.take() {
+            console.quit();
+        }
+        result = try_command(
+            console,
+            username,
+            &target,
+            &command,
+            &mut answers,
+            &replies,
+            &mut on_line,
+        );
+    }
+
+    let _ = replies.send(Reply::Done(result));
+}
+
+/// Runs a command in the session's SteamCMD, starting it and logging in if needed.
+fn try_command(
+    console: &mut Option<Console>,
+    username: &str,
+    target: &InstallTarget,
+    command: &str,
+    answers: &mut LoginAnswers,
+    replies: &Sender<Reply>,
+    on_line: &mut dyn FnMut(&str),
+) -> Result<SteamCmdOutput> {
+    if console
+        .as_mut()
+        .is_some_and(|console| !console.is_running())
+    {
+        info!("SteamCMD session has exited, starting it again...");
+        *console = None;
+    }
+
+    // There is no going back to SteamCMD's own folder once another one has been forced
+    if target.install_dir.is_none()
+        && console
+            .as_ref()
+            .is_some_and(|console| console.install_dir.is_some())
+    {
+        if let Some(console) = console.take() {
+            console.quit();
+        }
+    }
+
+    let current: &mut Console = match console {
+        Some(current) => current,
+        None => console.insert(Console::start(on_line).map_err(|e| {
+            warn!("steamcmd_session::try_command() -> {e:#}");
+            anyhow!(SessionUnavailable)
+        })?),
+    };
+    let _ = replies.send(Reply::Started(current.process.child.clone_killer()));
+
+    let result: Result<SteamCmdOutput> = current
+        .set_install_dir(target.install_dir.as_deref(), on_line)
+        .and_then(|_| current.set_platform(target.forced_platform(), on_line))
+        .and_then(|_| current.login(username, answers, on_line))
+        .and_then(|_| {
+            let lines: Vec<String> = current.run(command, answers, on_line)?;
+
+            // SteamCMD keeps running, so failures only show in the command's output
+            Ok(SteamCmdOutput {
+                status: ExitStatus::with_exit_code(0),
+                lines,
+            })
+        });
+
+    // A failed command leaves SteamCMD at its prompt, anything else leaves it unusable,
+    // such as still waiting for a Steam Guard code that never came
+    if let Err(e) = &result {
+        let usable: bool = match e.downcast_ref::<SteamCmdError>() {
+            Some(SteamCmdError::SteamGuardTimeout) | None => false,
+            Some(_) => current.is_running(),
+        };
+        if !usable {
+            if let Some(console) = console.take() {
+                console.kill();
+            }
+        }
+    }
+    result.with_context(|| "steamcmd_session::try_command() -> ")
+}
+
+/// Returns if a command failed because SteamCMD lost its connection to Steam.
+fn is_disconnected(result: &Result<SteamCmdOutput>) -> bool {
+    match result {
+        Ok(output) => steamcmd::check_output(output) == Err(SteamCmdError::NoConnection),
+        Err(e) => e.downcast_ref::<SteamCmdError>() == Some(&SteamCmdError::NoConnection),
+    }
+}
+
+/// The session's SteamCMD, read from by a thread of its own so it can be waited on.
+struct Console {
+    process: SteamCmdProcess,
+    output: Receiver<Output>,
+    logged_in: bool,
+    install_dir: Option<String>,
+    platform: String,
+}
+
+impl Console {
+    /// Starts SteamCMD and waits for it to finish updating itself and show its prompt.
+    fn start(on_line: &mut dyn FnMut(&str)) -> Result<Self> {
+        let mut process: SteamCmdProcess =
+            steamcmd::spawn(&[]).with_context(|| "steamcmd_session::Console::start() -> ")?;
+
+        let reader: Box<dyn Read + Send> = mem::replace(&mut process.reader, Box::new(io::empty()));
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || read_output(reader, sender));
+
+        let mut console: Self = Self {
+            process,
+            output,
+            logged_in: false,
+            install_dir: None,
+            platform: NATIVE_PLATFORM.to_string(),
+        };
+        console
+            .collect(&mut LoginAnswers::default(), on_line)
+            .with_context(|| "steamcmd_session::Console::start() -> ")?;
+        Ok(console)
+    }
+
+    /// Makes SteamCMD install to, and look for games in, install_dir, logging out if needed.
+    fn set_install_dir(
+        &mut self,
+        install_dir: Option<&str>,
+        on_line: &mut dyn FnMut(&str),
+    ) -> Result<()> {
+        let Some(install_dir) = install_dir else {
+            return Ok(());
+        };
+        if self.install_dir.as_deref() == Some(install_dir) {
+            return Ok(());
+        }
+        // The path is written into the console, where these would end the command early
+        if install_dir.contains(['"', '\n', '\r']) {
+            bail!("steamcmd_session::Console::set_install_dir() | Err: Install folder contains a quote or line break: {install_dir:?}")
+        }
+
+        let mut no_answers: LoginAnswers = LoginAnswers::default();
+        if self.logged_in {
+            self.run("logout", &mut no_answers, on_line)
+                .with_context(|| "steamcmd_session::Console::set_install_dir() -> ")?;
+            self.logged_in = false;
+        }
+        self.run(
+            &format!("force_install_dir \"{install_dir}\""),
+            &mut no_answers,
+            on_line,
+        )
+        .with_context(|| "steamcmd_session::Console::set_install_dir() -> ")?;
+
+        self.install_dir = Some(install_dir.to_string());
+        Ok(())
+    }
+
+    /// Makes SteamCMD download builds for platform, the one Monarch runs on if None.
+    fn set_platform(
+        &mut self,
+        platform: Option<&str>,
+        on_line: &mut dyn FnMut(&str),
+    ) -> Result<()> {
+        let platform: &str = platform.unwrap_or(NATIVE_PLATFORM);
+        if self.platform == platform {
+            return Ok(());
+        }
+
+        self.run(
+            &format!("@sSteamCmdForcePlatformType {platform}"),
+            &mut LoginAnswers::default(),
+            on_line,
+        )
+        .with_context(|| "steamcmd_session::Console::set_platform() -> ")?;
+
+        self.platform = platform.to_string();
+        Ok(())
+    }
+
+    fn login(
+        &mut self,
+        username: &str,
+        answers: &mut LoginAnswers,
+        on_line: &mut dyn FnMut(&str),
+    ) -> Result<()> {
+        if self.logged_in {
+            return Ok(());
+        }
+
+        let lines: Vec<String> = self
+            .run(&format!("login {username}"), answers, on_line)
+            .with_context(|| "steamcmd_session::Console::login() -> ")?;
+        steamcmd::check_output(&SteamCmdOutput {
+            status: ExitStatus::with_exit_code(0),
+            lines,
+        })?;
+
+        self.logged_in = true;
+        Ok(())
+    }
+
+    /// Runs a command and returns its output.
+    fn run(
+        &mut self,
+        command: &str,
+        answers: &mut LoginAnswers,
+        on_line: &mut dyn FnMut(&str),
+    ) -> Result<Vec<String>> {
+        steamcmd::write_input(&self.process.input, command)
+            .with_context(|| "steamcmd_session::Console::run() -> ")?;
+        let result: Result<Vec<String>> = self.collect(answers, on_line);
+
+        steamcmd::stop_waiting(&self.process.input);
+        result
+    }
+
+    /// Collects output until SteamCMD is back at its prompt, answering prompts on the way.
+    fn collect(
+        &mut self,
+        answers: &mut LoginAnswers,
+        on_line: &mut dyn FnMut(&str),
+    ) -> Result<Vec<String>> {
+        let mut lines: VecDeque<String> = VecDeque::new();
+        let mut code_deadline: Option<Instant> = None;
+
+        loop {
+            let received: Result<Output, RecvTimeoutError> = match code_deadline {
+                Some(deadline) => self
+                    .output
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => self
+                    .output
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+            };
+
+            let output: Output = match received {
+                Ok(output) => output,
+                // A submitted code can take a moment to be answered
+                Err(RecvTimeoutError::Timeout)
+                    if !steamcmd::is_waiting_for_code(&self.process.input) =>
+                {
+                    code_deadline = None;
+                    continue;
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    warn!("steamcmd_session::Console::collect() No Steam Guard code entered in time! Stopping...");
+                    steamcmd::stop_waiting(&self.process.input);
+                    return Err(SteamCmdError::SteamGuardTimeout.into());
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    bail!("steamcmd_session::Console::collect() | Err: SteamCMD exited!")
+                }
+            };
+            code_deadline = None;
+
+            let line: String = match output {
+                Output::Prompt(prompt) if steamcmd::is_console_prompt(&prompt) => {
+                    return Ok(lines.into());
+                }
+                Output::Prompt(prompt) => {
+                    steamcmd::answer_prompt(&prompt, answers, &self.process.input)
+                        .with_context(|| "steamcmd_session::Console::collect() -> ")?;
+
+                    // The session is blocked until the user enters the code, if ever
+                    if steamcmd::is_waiting_for_code(&self.process.input) {
+                        code_deadline = Some(Instant::now() + STEAM_GUARD_TIMEOUT);
+                    }
+                    prompt
+                }
+                Output::Line(line) => line,
+            };
+
+            keep_line(&mut lines, &line, on_line);
+        }
+    }
+
+    fn is_running(&mut self) -> bool {
+        matches!(self.process.child.try_wait(), Ok(None))
+    }
+
+    /// Asks SteamCMD to log out and exit, killing it if it can't be asked.
+    fn quit(mut self) {
+        if steamcmd::write_input(&self.process.input, "quit").is_err() {
+            return self.kill();
+        }
+        if let Err(e) = self.process.child.wait() {
+            warn!("steamcmd_session::Console::quit() Failed to wait for SteamCMD! | Err: {e}");
+        }
+    }
+
+    fn kill(mut self) {
+        if let Err(e) = self.process.child.kill() {
+            warn!("steamcmd_session::Console::kill() Failed to kill SteamCMD! | Err: {e}");
+        }
+        let _ = self.process.child.wait();
+    }
+}
+
+/// Passes a line of output on and keeps it, with the branch password of the command SteamCMD
+/// echoes masked.
+pub(super) fn keep_line(lines: &mut VecDeque<String>, line: &str, on_line: &mut dyn FnMut(&str)) {
+    let line: String = steamcmd::redact(line);
+    on_line(&line);
+    if lines.len() == KEPT_LINES {
+        lines.pop_front();
+    }
+    lines.push_back(line);
+}
+
+/// Passes output on until SteamCMD exits or the session stops listening.
+fn read_output(mut reader: Box<dyn Read + Send>, output: Sender<Output>) {
+    let mut splitter: OutputSplitter = OutputSplitter::default();
+    let mut buffer: [u8; 4096] = [0; 4096];
+
+    // Reading fails instead of ending on some platforms once SteamCMD has exited
+    while let Ok(read) = reader.read(&mut buffer) {
+        if read == 0 {
+            break;
+        }
+        for split in splitter.push(&buffer[..read]) {
+            if output.send(split).is_err() {
+                return;
+            }
+        }
+    }
+    if let Some(rest) = splitter.finish() {
+        let _ = output.send(Output::Line(rest));
+    }
+}
+
+fn lock_session() -> MutexGuard<'static, Option<Session>> {
+    SESSION.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use reqwest::Response;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::fs::File;
 use std::io::Write;
@@ -114,15 +115,23 @@ pub async fn install_steamcmd(handle: &AppHandle) -> Result<()> {
     Ok(())
 }
 
+/// Returns path to the SteamCMD executable installed by Monarch
+pub fn steamcmd_executable() -> PathBuf {
+    get_steamcmd_dir().join("steamcmd").join("steamcmd.exe")
+}
+
 /// Runs specified command via SteamCMD and waits for it to finish
 /// before returning.
-pub async fn steamcmd_command(handle: &AppHandle, args: Vec<&str>) -> Result<()> {
-    let mut path: PathBuf = get_steamcmd_dir();
-    path.push("steamcmd");
-    path.push("steamcmd.exe");
+pub async fn steamcmd_command(
+    handle: &AppHandle,
+    args: Vec<&str>,
+    env: &BTreeMap<String, String>,
+) -> Result<()> {
+    let path: PathBuf = steamcmd_executable();
     let args_string: String = args.iter().map(|arg| format!("{arg} ")).collect::<String>();
 
-    run_in_terminal(handle, &format!("{} {}", path.display(), args_string), None)
+    let env_vars: HashMap<&str, &str> = env.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    run_in_terminal(handle, &format!("{} {}", path.display(), args_string), Some(env_vars))
         .await
         .with_context(|| "windows::steam::steamcmd_command() -> ")?;
 
@@ -171,10 +180,11 @@ pub fn get_default_libraryfolders_location() -> Result<PathBuf> {
 }
 
 /// Runs specified command via Steam
-pub fn run_command(args: &str) -> Result<()> {
+pub fn run_command(args: &str, env: &BTreeMap<String, String>) -> Result<()> {
     Command::new("powershell.exe")
         .arg("start")
         .arg(args)
+        .envs(env)
         .spawn()
         .with_context(|| {
             format!(
@@ -0,0 +1,484 @@
+/*
+* Rule language for smart collections.
+*
+* A rule is one or more conditions combined with "and", "or", "not" and parentheses:
+*
+*   platform = steam and compat
+*   last_played > 30d or not played
+*   playtime < 1h and name ~ "^Half-Life"
+*
+* Conditions:
+*   platform = <name> | platform != <name>    Platform the game is installed through
+*   compat                                    Game uses a compatibility layer, such as Proton
+*   played                                    Game has been launched through Monarch
+*   last_played <op> <duration>               Time since last launch, never played counts as forever
+*   playtime <op> <duration>                  Total time played through Monarch
+*   name ~ <regex>                            Name matches a regular expression
+*   tag = <tag> | tag != <tag>                Game has, or doesn't have, a tag
+*   favorite                                  Game is marked as a favorite
+*   hidden                                    Game is hidden from the library
+*
+* <op> is one of <, <=, >, >= and durations are written as a number followed by s, m, h, d or w.
+* Values containing spaces or special characters can be put in double quotes.
+*/
+
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+use std::fmt;
+
+use super::play_history::{self, GameHistory};
+use crate::monarch_games::monarchgame::MonarchGame;
+
+/// Error returned when a rule can't be parsed, meant to be shown to the user.
+#[derive(Debug)]
+pub struct RuleError(String);
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+#[derive(Debug, Clone)]
+pub enum Rule {
+    And(Box<Rule>, Box<Rule>),
+    Or(Box<Rule>, Box<Rule>),
+    Not(Box<Rule>),
+    Condition(Condition),
+}
+
+#[derive(Debug, Clone)]
+pub enum Condition {
+    Platform { equals: bool, platform: String },
+    Compat,
+    Played,
+    LastPlayed { op: Comparison, seconds: u64 },
+    Playtime { op: Comparison, seconds: u64 },
+    Name(Regex),
+    Tag { equals: bool, tag: String },
+    Favorite,
+    Hidden,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn compare(self, left: u64, right: u64) -> bool {
+        match self {
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+/// Everything besides the game itself that rules can look at.
+pub struct RuleContext {
+    history: HashMap<String, GameHistory>,
+    now: u64,
+}
+
+impl RuleContext {
+    pub fn load() -> Self {
+        Self {
+            history: play_history::get_history(),
+            now: play_history::unix_now(),
+        }
+    }
+}
+
+impl Rule {
+    /// Parses a rule written by the user.
+    pub fn parse(input: &str) -> Result<Self, RuleError> {
+        let tokens: Vec<RuleToken> = tokenize(input)?;
+        if tokens.is_empty() {
+            return Err(RuleError(String::from("Rule is empty!")));
+        }
+
+        let mut parser = Parser { tokens, pos: 0 };
+        let rule: Rule = parser.parse_or()?;
+
+        match parser.peek() {
+            None => Ok(rule),
+            Some(token) => Err(RuleError(format!("Unexpected '{token}' in rule!"))),
+        }
+    }
+
+    /// Returns whether a game belongs in a collection with this rule.
+    pub fn matches(&self, game: &MonarchGame, ctx: &RuleContext) -> bool {
+        match self {
+            Rule::And(left, right) => left.matches(game, ctx) && right.matches(game, ctx),
+            Rule::Or(left, right) => left.matches(game, ctx) || right.matches(game, ctx),
+            Rule::Not(rule) => !rule.matches(game, ctx),
+            Rule::Condition(condition) => condition.matches(game, ctx),
+        }
+    }
+}
+
+impl Condition {
+    fn matches(&self, game: &MonarchGame, ctx: &RuleContext) -> bool {
+        let history: Option<&GameHistory> = ctx.history.get(&game.id);
+
+        match self {
+            Condition::Platform { equals, platform } => {
+                let game_platform: &str = match game.platform.as_str() {
+                    "steamcmd" => "steam",
+                    p => p,
+                };
+                let wanted: &str = match platform.as_str() {
+                    "steamcmd" => "steam",
+                    p => p,
+                };
+                (game_platform == wanted) == *equals
+            }
+            Condition::Compat => !game.compatibility.is_empty(),
+            Condition::Played => history.is_some_and(|h| h.launch_count > 0),
+            Condition::LastPlayed { op, seconds } => {
+                let since: u64 = match history {
+                    Some(h) if h.last_played > 0 => ctx.now.saturating_sub(h.last_played),
+                    _ => u64::MAX,
+                };
+                op.compare(since, *seconds)
+            }
+            Condition::Playtime { op, seconds } => {
+                let playtime: u64 = history.map(|h| h.playtime).unwrap_or(0);
+                op.compare(playtime, *seconds)
+            }
+            Condition::Name(regex) => regex.is_match(&game.name),
+            Condition::Tag { equals, tag } => {
+                game.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) == *equals
+            }
+            Condition::Favorite => game.favorite,
+            Condition::Hidden => game.hidden,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum RuleToken {
+    Word(String),
+    Quoted(String),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+impl fmt::Display for RuleToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleToken::Word(word) => write!(f, "{word}"),
+            RuleToken::Quoted(text) => write!(f, "\"{text}\""),
+            RuleToken::Op(op) => write!(f, "{op}"),
+            RuleToken::Open => write!(f, "("),
+            RuleToken::Close => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<RuleToken>, RuleError> {
+    let mut tokens: Vec<RuleToken> = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(RuleToken::Open),
+            ')' => tokens.push(RuleToken::Close),
+            '"' => {
+                let mut text: String = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => text.push(escaped),
+                            None => return Err(RuleError(String::from("Unclosed quote in rule!"))),
+                        },
+                        Some(c) => text.push(c),
+                        None => return Err(RuleError(String::from("Unclosed quote in rule!"))),
+                    }
+                }
+                tokens.push(RuleToken::Quoted(text));
+            }
+            '=' | '~' => tokens.push(RuleToken::Op(if c == '=' { "=" } else { "~" })),
+            '!' | '<' | '>' => {
+                let op: &'static str = match (c, chars.peek()) {
+                    ('!', Some('=')) => "!=",
+                    ('<', Some('=')) => "<=",
+                    ('>', Some('=')) => ">=",
+                    ('<', _) => "<",
+                    ('>', _) => ">",
+                    _ => return Err(RuleError(String::from("Expected '=' after '!' in rule!"))),
+                };
+                if op.len() == 2 {
+                    chars.next();
+                }
+                tokens.push(RuleToken::Op(op));
+            }
+            _ => {
+                let mut word: String = String::from(c);
+                while let Some(next) = chars.peek() {
+                    if next.is_whitespace() || "()\"=~!<>".contains(*next) {
+                        break;
+                    }
+                    word.push(*next);
+                    chars.next();
+                }
+                tokens.push(RuleToken::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<RuleToken>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&RuleToken> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<RuleToken> {
+        let token: Option<RuleToken> = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(RuleToken::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn parse_or(&mut self) -> Result<Rule, RuleError> {
+        let mut rule: Rule = self.parse_and()?;
+        while self.peek_keyword("or") {
+            self.next();
+            rule = Rule::Or(Box::new(rule), Box::new(self.parse_and()?));
+        }
+        Ok(rule)
+    }
+
+    fn parse_and(&mut self) -> Result<Rule, RuleError> {
+        let mut rule: Rule = self.parse_not()?;
+        while self.peek_keyword("and") {
+            self.next();
+            rule = Rule::And(Box::new(rule), Box::new(self.parse_not()?));
+        }
+        Ok(rule)
+    }
+
+    fn parse_not(&mut self) -> Result<Rule, RuleError> {
+        if self.peek_keyword("not") {
+            self.next();
+            return Ok(Rule::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_atom()
+    }
+
+    fn parse_atom(&mut self) -> Result<Rule, RuleError> {
+        match self.next() {
+            Some(RuleToken::Open) => {
+                let rule: Rule = self.parse_or()?;
+                match self.next() {
+                    Some(RuleToken::Close) => Ok(rule),
+                    _ => Err(RuleError(String::from("Missing ')' in rule!"))),
+                }
+            }
+            Some(RuleToken::Word(field)) => self
+                .parse_condition(&field.to_lowercase())
+                .map(Rule::Condition),
+            Some(token) => Err(RuleError(format!("Unexpected '{token}' in rule!"))),
+            None => Err(RuleError(String::from("Rule ends unexpectedly!"))),
+        }
+    }
+
+    fn parse_condition(&mut self, field: &str) -> Result<Condition, RuleError> {
+        match field {
+            "platform" => {
+                let equals: bool = match self.expect_op()? {
+                    "=" => true,
+                    "!=" => false,
+                    op => return Err(RuleError(format!("Can't use '{op}' with platform!"))),
+                };
+                let platform: String = self.expect_value()?.to_lowercase();
+                Ok(Condition::Platform { equals, platform })
+            }
+            "tag" => {
+                let equals: bool = match self.expect_op()? {
+                    "=" => true,
+                    "!=" => false,
+                    op => return Err(RuleError(format!("Can't use '{op}' with tag!"))),
+                };
+                let tag: String = self.expect_value()?;
+                Ok(Condition::Tag { equals, tag })
+            }
+            "favorite" => Ok(Condition::Favorite),
+            "hidden" => Ok(Condition::Hidden),
+            "compat" => Ok(Condition::Compat),
+            "played" => Ok(Condition::Played),
+            "last_played" | "playtime" => {
+                let op: Comparison = match self.expect_op()? {
+                    "<" => Comparison::Less,
+                    "<=" => Comparison::LessOrEqual,
+                    ">" => Comparison::Greater,
+                    ">=" => Comparison::GreaterOrEqual,
+                    op => return Err(RuleError(format!("Can't use '{op}' with {field}!"))),
+                };
+                let seconds: u64 = parse_duration(&self.expect_value()?)?;
+
+                if field == "playtime" {
+                    Ok(Condition::Playtime { op, seconds })
+                } else {
+                    Ok(Condition::LastPlayed { op, seconds })
+                }
+            }
+            "name" => {
+                if self.expect_op()? != "~" {
+                    return Err(RuleError(String::from("Use '~' to match names, e.g. name ~ \"^Half\"")));
+                }
+                let pattern: String = self.expect_value()?;
+                let regex: Regex = RegexBuilder::new(&pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| RuleError(format!("Invalid name pattern: {e}")))?;
+                Ok(Condition::Name(regex))
+            }
+            _ => Err(RuleError(format!("Unknown condition '{field}' in rule!"))),
+        }
+    }
+
+    fn expect_op(&mut self) -> Result<&'static str, RuleError> {
+        match self.next() {
+            Some(RuleToken::Op(op)) => Ok(op),
+            Some(token) => Err(RuleError(format!("Expected an operator, found '{token}'!"))),
+            None => Err(RuleError(String::from("Rule ends unexpectedly!"))),
+        }
+    }
+
+    fn expect_value(&mut self) -> Result<String, RuleError> {
+        match self.next() {
+            Some(RuleToken::Word(value)) | Some(RuleToken::Quoted(value)) => Ok(value),
+            Some(token) => Err(RuleError(format!("Expected a value, found '{token}'!"))),
+            None => Err(RuleError(String::from("Rule ends unexpectedly!"))),
+        }
+    }
+}
+
+/// Parses durations such as 30d, 1h or 90m into seconds.
+fn parse_duration(value: &str) -> Result<u64, RuleError> {
+    let invalid = || RuleError(format!("Invalid duration '{value}', use e.g. 45m, 1h or 30d!"));
+
+    let unit_start: usize = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let amount: u64 = value[..unit_start].parse().map_err(|_| invalid())?;
+
+    let multiplier: u64 = match &value[unit_start..] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 604800,
+        _ => return Err(invalid()),
+    };
+    Ok(amount.saturating_mul(multiplier))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(name: &str, platform: &str) -> MonarchGame {
+        MonarchGame {
+            id: name.to_lowercase(),
+            name: name.to_string(),
+            platform: platform.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn context(history: &[(&str, GameHistory)]) -> RuleContext {
+        RuleContext {
+            history: history
+                .iter()
+                .map(|(id, h)| (id.to_string(), h.clone()))
+                .collect(),
+            now: 10 * 86400,
+        }
+    }
+
+    fn matches(rule: &str, game: &MonarchGame, ctx: &RuleContext) -> bool {
+        Rule::parse(rule).unwrap().matches(game, ctx)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let ctx: RuleContext = context(&[]);
+        let mut portal: MonarchGame = game("Portal", "steamcmd");
+        portal.favorite = true;
+
+        assert!(matches("platform = steam and favorite", &portal, &ctx));
+        assert!(matches("hidden and compat or favorite", &portal, &ctx));
+        assert!(!matches("hidden and (compat or favorite)", &portal, &ctx));
+        assert!(matches("not hidden AND NOT compat", &portal, &ctx));
+        assert!(!matches("platform != steam", &portal, &ctx));
+    }
+
+    #[test]
+    fn never_played_games_count_as_played_forever_ago() {
+        let played: GameHistory = GameHistory {
+            launch_count: 1,
+            last_played: 9 * 86400,
+            playtime: 5400,
+            ..Default::default()
+        };
+        let ctx: RuleContext = context(&[("portal", played)]);
+        let portal: MonarchGame = game("Portal", "steam");
+        let celeste: MonarchGame = game("Celeste", "steam");
+
+        assert!(matches("last_played < 2d", &portal, &ctx));
+        assert!(matches("last_played > 30d", &celeste, &ctx));
+        assert!(matches("playtime >= 1h and played", &portal, &ctx));
+        assert!(matches("not played", &celeste, &ctx));
+    }
+
+    #[test]
+    fn names_and_tags_match_quoted_values() {
+        let ctx: RuleContext = context(&[]);
+        let mut half_life: MonarchGame = game("Half-Life 2", "steam");
+        half_life.tags = vec![String::from("Co-op Night")];
+
+        assert!(matches(r#"name ~ "^half-life \\d$""#, &half_life, &ctx));
+        assert!(matches(r#"tag = "co-op night""#, &half_life, &ctx));
+        assert!(!matches(r#"tag != "Co-op Night""#, &half_life, &ctx));
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        for rule in [
+            "",
+            "platform steam",
+            "playtime > 1y",
+            "name = Portal",
+            "(favorite",
+            "favorite hidden",
+            r#"name ~ "unclosed"#,
+            "rating > 5",
+        ] {
+            assert!(Rule::parse(rule).is_err(), "Parsed invalid rule: {rule}");
+        }
+        assert_eq!(parse_duration("90m").unwrap(), 5400);
+        assert_eq!(parse_duration("2w").unwrap(), 1209600);
+    }
+}
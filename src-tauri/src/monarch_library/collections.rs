@@ -1,13 +1,16 @@
 use anyhow::{bail, Context, Result};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, value::Value};
-use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
 use std::fs;
-use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
 use tracing::{error, info};
 
+use super::collection_rules::{Rule, RuleContext};
+use crate::monarch_games::monarchgame::MonarchGame;
 use crate::monarch_utils::monarch_fs::{get_collections_json_path, write_json_content};
+use crate::monarch_utils::monarch_state::MONARCH_STATE;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[allow(non_snake_case)]
@@ -15,26 +18,56 @@ struct MonarchCollection {
     id: String,
     name: String,
     gameIds: Vec<String>, // camelCase to work better with frontend and json
+    #[serde(default)]
+    rule: Option<String>, // Smart collections get their games from a rule instead of gameIds
+    #[serde(default)]
+    order: u32, // Position among collections with the same parent
+    #[serde(default)]
+    parentId: Option<String>,
 }
 
 impl MonarchCollection {
-    pub fn new(name: &str, games: Vec<String>) -> Self {
+    pub fn new(
+        name: &str,
+        games: Vec<String>,
+        rule: Option<String>,
+        parent_id: Option<String>,
+    ) -> Self {
         Self {
-            id: generate_hash(&name.to_string()).to_string(),
+            id: generate_uuid(),
             name: name.to_string(),
             gameIds: games,
+            rule,
+            order: 0,
+            parentId: parent_id,
         }
     }
 }
 
 /// Creates a new collection.
-pub fn new_collection(collection_name: String, game_ids: Vec<String>) -> Result<Value> {
+pub fn new_collection(
+    collection_name: String,
+    game_ids: Vec<String>,
+    rule: Option<String>,
+    parent_id: Option<String>,
+) -> Result<Value> {
     let path: PathBuf = get_collections_json_path();
-    let new_collec: MonarchCollection = MonarchCollection::new(&collection_name, game_ids);
+    let rule: Option<String> =
+        validate_rule(rule).with_context(|| "collections::new_collection() -> ")?;
 
     let mut collecs: Vec<MonarchCollection> =
         get_collections_as_struct().with_context(|| "collections::new_collection() -> ")?;
 
+    if let Some(parent) = &parent_id {
+        if find_collection_index(parent, &collecs).is_none() {
+            bail!("collections::new_collection() No parent collection found with id: {parent}")
+        }
+    }
+
+    let mut new_collec: MonarchCollection =
+        MonarchCollection::new(&collection_name, dedup_ids(game_ids), rule, parent_id);
+    new_collec.order = next_order(&new_collec.parentId, &collecs);
+
     collecs.push(new_collec);
     write_json_content(json!(collecs), &path)
         .with_context(|| "collections::new_collection() -> ")?;
@@ -42,7 +75,14 @@ pub fn new_collection(collection_name: String, game_ids: Vec<String>) -> Result<
 }
 
 /// Updates info about a collection.
-pub fn update_collections(id: &str, new_name: &str, game_ids: Vec<String>) -> Result<Value> {
+pub fn update_collections(
+    id: &str,
+    new_name: &str,
+    game_ids: Vec<String>,
+    rule: Option<String>,
+) -> Result<Value> {
+    let rule: Option<String> =
+        validate_rule(rule).with_context(|| "collections::update_collections() -> ")?;
     let mut collecs: Vec<MonarchCollection> =
         get_collections_as_struct().with_context(|| "collections::update_collections() -> ")?;
 
@@ -52,7 +92,12 @@ pub fn update_collections(id: &str, new_name: &str, game_ids: Vec<String>) -> Re
             .with_context(|| "collections::update_collections() -> ")?;
 
         collection.name = new_name.to_string();
-        collection.gameIds = game_ids;
+        collection.rule = rule;
+        collection.gameIds = if collection.rule.is_some() {
+            Vec::new()
+        } else {
+            dedup_ids(game_ids)
+        };
 
         write_collection_changes(json!(collecs))
             .with_context(|| "collections::update_collections() -> ")?;
@@ -62,13 +107,28 @@ pub fn update_collections(id: &str, new_name: &str, game_ids: Vec<String>) -> Re
     bail!("collections::update_collections() No index found for collection: {id}")
 }
 
-/// Deletes a specified collection
+/// Deletes a specified collection, any collections inside it are moved up to its parent.
 pub fn delete_collections(id: &str) -> Result<Value> {
     let mut collecs =
         get_collections_as_struct().with_context(|| "collections::delete_collections() -> ")?;
 
     if let Some(index) = find_collection_index(id, &collecs) {
-        collecs.remove(index);
+        let removed: MonarchCollection = collecs.remove(index);
+        let children: u32 = collecs
+            .iter()
+            .filter(|collec| collec.parentId.as_deref() == Some(id))
+            .count() as u32;
+
+        // Children take the place of the deleted collection
+        for collec in collecs.iter_mut() {
+            if collec.parentId.as_deref() == Some(id) {
+                collec.parentId = removed.parentId.clone();
+                collec.order += removed.order;
+            } else if collec.parentId == removed.parentId && collec.order > removed.order {
+                collec.order += children;
+            }
+        }
+        normalize_order(&mut collecs);
 
         write_collection_changes(json!(collecs))
             .with_context(|| "collections::delete_collections() -> ")?;
@@ -78,25 +138,173 @@ pub fn delete_collections(id: &str) -> Result<Value> {
     bail!("collections::delete_collections() No index found for collection: {id}")
 }
 
-/// Returns JSON of collections in library
+/// Sets the order of collections sharing a parent, ids are given in their new order.
+pub fn reorder_collections(ids: Vec<String>) -> Result<Value> {
+    let mut collecs: Vec<MonarchCollection> =
+        get_collections_as_struct().with_context(|| "collections::reorder_collections() -> ")?;
+
+    let mut parent: Option<Option<String>> = None;
+    for (position, id) in ids.iter().enumerate() {
+        let index: usize = match find_collection_index(id, &collecs) {
+            Some(index) => index,
+            None => bail!("collections::reorder_collections() No index found for collection: {id}"),
+        };
+
+        let collec: &mut MonarchCollection = &mut collecs[index];
+        match &parent {
+            Some(parent_id) if *parent_id != collec.parentId => {
+                bail!("collections::reorder_collections() | Err: Collections don't share the same parent!")
+            }
+            _ => parent = Some(collec.parentId.clone()),
+        }
+        collec.order = position as u32;
+    }
+
+    // Collections left out of ids keep their relative order after the given ones
+    if let Some(parent_id) = parent {
+        let mut rest: Vec<&mut MonarchCollection> = collecs
+            .iter_mut()
+            .filter(|collec| collec.parentId == parent_id && !ids.contains(&collec.id))
+            .collect();
+        rest.sort_by_key(|collec| collec.order);
+
+        for (position, collec) in (ids.len() as u32..).zip(rest) {
+            collec.order = position;
+        }
+    }
+
+    write_collection_changes(json!(collecs))
+        .with_context(|| "collections::reorder_collections() -> ")?;
+    get_collections().with_context(|| "collections::reorder_collections() -> ")
+}
+
+/// Moves a collection inside another one, or to the top level if parent_id is None.
+pub fn move_collection(id: &str, parent_id: Option<String>) -> Result<Value> {
+    let mut collecs: Vec<MonarchCollection> =
+        get_collections_as_struct().with_context(|| "collections::move_collection() -> ")?;
+
+    let index: usize = match find_collection_index(id, &collecs) {
+        Some(index) => index,
+        None => bail!("collections::move_collection() No index found for collection: {id}"),
+    };
+
+    if let Some(parent) = &parent_id {
+        if find_collection_index(parent, &collecs).is_none() {
+            bail!("collections::move_collection() No parent collection found with id: {parent}")
+        }
+        if is_descendant_of(parent, id, &collecs) {
+            bail!("collections::move_collection() | Err: Can't move a collection inside itself!")
+        }
+    }
+
+    collecs[index].order = next_order(&parent_id, &collecs);
+    collecs[index].parentId = parent_id;
+    normalize_order(&mut collecs);
+
+    write_collection_changes(json!(collecs))
+        .with_context(|| "collections::move_collection() -> ")?;
+    get_collections().with_context(|| "collections::move_collection() -> ")
+}
+
+/// Sets the order of games within a collection. Has to contain the same games as before.
+pub fn reorder_collection_games(id: &str, game_ids: Vec<String>) -> Result<Value> {
+    let mut collecs: Vec<MonarchCollection> = get_collections_as_struct()
+        .with_context(|| "collections::reorder_collection_games() -> ")?;
+
+    let index: usize = match find_collection_index(id, &collecs) {
+        Some(index) => index,
+        None => bail!("collections::reorder_collection_games() No index found for collection: {id}"),
+    };
+    let collec: &mut MonarchCollection = &mut collecs[index];
+
+    if collec.rule.is_some() {
+        bail!("collections::reorder_collection_games() | Err: Games in smart collections can't be reordered!")
+    }
+
+    let game_ids: Vec<String> = dedup_ids(game_ids);
+    let current: HashSet<&String> = collec.gameIds.iter().collect();
+    if game_ids.len() != current.len() || !game_ids.iter().all(|game| current.contains(game)) {
+        bail!("collections::reorder_collection_games() | Err: Reordered games don't match the games in collection: {id}")
+    }
+    collec.gameIds = game_ids;
+
+    write_collection_changes(json!(collecs))
+        .with_context(|| "collections::reorder_collection_games() -> ")?;
+    get_collections().with_context(|| "collections::reorder_collection_games() -> ")
+}
+
+/// Removes a game from every collection, used when a game is removed from the library.
+pub fn remove_game_from_collections(game_id: &str) -> Result<()> {
+    let mut collecs: Vec<MonarchCollection> = get_collections_as_struct()
+        .with_context(|| "collections::remove_game_from_collections() -> ")?;
+
+    let mut changed: bool = false;
+    for collec in collecs.iter_mut() {
+        let len: usize = collec.gameIds.len();
+        collec.gameIds.retain(|id| id != game_id);
+        changed |= collec.gameIds.len() != len;
+    }
+
+    if changed {
+        write_collection_changes(json!(collecs))
+            .with_context(|| "collections::remove_game_from_collections() -> ")?;
+    }
+    Ok(())
+}
+
+/// Returns JSON of collections in library, with the games of smart collections filled in.
 pub fn get_collections() -> Result<Value> {
+    let mut collecs: Vec<MonarchCollection> =
+        get_collections_as_struct().with_context(|| "collections::get_collections() -> ")?;
+
+    if collecs.iter().any(|collec| collec.rule.is_some()) {
+        let games: Vec<MonarchGame>;
+        unsafe {
+            games = MONARCH_STATE.get_library_games();
+        }
+        let ctx: RuleContext = RuleContext::load();
+
+        for collec in collecs.iter_mut() {
+            let rule: Rule = match collec.rule.as_deref().map(Rule::parse) {
+                Some(Ok(rule)) => rule,
+                Some(Err(e)) => {
+                    error!("collections::get_collections() Invalid rule in collection: {} | Err: {e}", collec.name);
+                    continue;
+                }
+                None => continue,
+            };
+
+            collec.gameIds = games
+                .iter()
+                .filter(|game| rule.matches(game, &ctx))
+                .map(|game| game.id.clone())
+                .collect();
+        }
+    }
+
+    collecs.sort_by_key(|collec| collec.order);
+    Ok(json!(collecs))
+}
+
+/// Returns JSON of collections as stored in collections.json
+fn read_collections() -> Result<Value> {
     let path: PathBuf = get_collections_json_path();
 
     match fs::File::open(&path) {
         Ok(file) => {
-            serde_json::from_reader(file).with_context(|| "collections::get_collections() Failed to parse file content to json! Possibly reading an empty file. | Err: ")
+            serde_json::from_reader(file).with_context(|| "collections::read_collections() Failed to parse file content to json! Possibly reading an empty file. | Err: ")
 
         } Err(e) => {
-            error!("collections::get_collections() Could not open: {file} | Err: {e}", file = path.display());
+            error!("collections::read_collections() Could not open: {file} | Err: {e}", file = path.display());
             info!("Attempting to create a new empty file: {file}", file = path.display());
 
             let monarch_collecs: Value = json!(Vec::<MonarchCollection>::new());
         
             fs::File::create(&path)
-                .with_context(|| format!("collections::get_collections() Failed to create: {file} | Err: {e}", file = path.display()))?;
+                .with_context(|| format!("collections::read_collections() Failed to create: {file} | Err: {e}", file = path.display()))?;
             
             write_json_content(monarch_collecs.clone(), &path)
-                .with_context(|| "collections::get_collections() -> ")?;
+                .with_context(|| "collections::read_collections() -> ")?;
             
             Ok(monarch_collecs) // If it succeeds at creating new collections.json
         }
@@ -114,19 +322,146 @@ fn write_collection_changes(collections: Value) -> Result<()> {
     Ok(())
 }
 
-/// Creates a unique hash for a MonarchCollection currently only based on its name
-fn generate_hash<T: Hash>(name: &T) -> u64 {
-    let mut hasher: DefaultHasher = DefaultHasher::new();
-    name.hash(&mut hasher);
+/// Generates a random (version 4) UUID, used as id for collections and merged games.
+pub fn generate_uuid() -> String {
+    let mut bytes: [u8; 16] = [0; 16];
+    rand::rng().fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40; // Version 4
+    bytes[8] = (bytes[8] & 0x3f) | 0x80; // Variant 1
+
+    let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
 
-    hasher.finish()
+fn is_uuid(id: &str) -> bool {
+    id.len() == 36
+        && id.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
 }
 
 /// Returns a Vec<MonarchCollection> instead of a json value to remove indentation in functions above.
 fn get_collections_as_struct() -> Result<Vec<MonarchCollection>> {
     let collecs_json =
-        get_collections().with_context(|| "collections::get_collections_as_struct() -> ")?;
-    serde_json::from_value::<Vec<MonarchCollection>>(collecs_json).with_context(|| "collections::get_collections_as_struct() Error while parsing from json to Vec<MonarchGame>! | Err: ")
+        read_collections().with_context(|| "collections::get_collections_as_struct() -> ")?;
+    let mut collecs: Vec<MonarchCollection> = serde_json::from_value::<Vec<MonarchCollection>>(collecs_json).with_context(|| "collections::get_collections_as_struct() Error while parsing from json to Vec<MonarchGame>! | Err: ")?;
+
+    if migrate_collections(&mut collecs) {
+        info!("Migrating collections.json to new format...");
+        write_collection_changes(json!(collecs))
+            .with_context(|| "collections::get_collections_as_struct() -> ")?;
+    }
+    Ok(collecs)
+}
+
+/// Older versions of Monarch gave collections ids hashed from their names, which could collide.
+/// Gives those collections UUIDs and fixes orders and parents. Returns whether anything changed.
+fn migrate_collections(collecs: &mut [MonarchCollection]) -> bool {
+    let mut changed: bool = false;
+    let mut seen: HashSet<String> = HashSet::new();
+
+    for collec in collecs.iter_mut() {
+        if !is_uuid(&collec.id) || !seen.insert(collec.id.clone()) {
+            collec.id = generate_uuid();
+            seen.insert(collec.id.clone());
+            changed = true;
+        }
+    }
+
+    // Parents that no longer exist or would form a loop are dropped
+    for i in 0..collecs.len() {
+        let broken: bool = match &collecs[i].parentId {
+            Some(parent) => {
+                find_collection_index(parent, collecs).is_none()
+                    || is_descendant_of(parent, &collecs[i].id, collecs)
+            }
+            None => false,
+        };
+        if broken {
+            collecs[i].parentId = None;
+            changed = true;
+        }
+    }
+
+    let before: Vec<u32> = collecs.iter().map(|collec| collec.order).collect();
+    normalize_order(collecs);
+    changed || collecs.iter().map(|collec| collec.order).ne(before)
+}
+
+/// Returns whether a collection is the same as, or nested inside, ancestor.
+fn is_descendant_of(id: &str, ancestor: &str, collecs: &[MonarchCollection]) -> bool {
+    let mut current: Option<&str> = Some(id);
+
+    // Depth is limited in case collections.json already contains a loop
+    for _ in 0..=collecs.len() {
+        match current {
+            Some(c) if c == ancestor => return true,
+            Some(c) => {
+                current = find_collection_index(c, collecs)
+                    .and_then(|index| collecs[index].parentId.as_deref())
+            }
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Numbers collections with the same parent 0, 1, 2... keeping their current order.
+fn normalize_order(collecs: &mut [MonarchCollection]) {
+    let mut indices: Vec<usize> = (0..collecs.len()).collect();
+    indices.sort_by_key(|&i| (collecs[i].parentId.clone(), collecs[i].order, i));
+
+    let mut position: u32 = 0;
+    let mut last_parent: Option<&Option<String>> = None;
+    let mut orders: Vec<(usize, u32)> = Vec::with_capacity(indices.len());
+
+    for &i in indices.iter() {
+        if last_parent != Some(&collecs[i].parentId) {
+            position = 0;
+            last_parent = Some(&collecs[i].parentId);
+        }
+        orders.push((i, position));
+        position += 1;
+    }
+
+    for (i, order) in orders {
+        collecs[i].order = order;
+    }
+}
+
+/// Returns the order to give a collection added to the end of parent.
+fn next_order(parent_id: &Option<String>, collecs: &[MonarchCollection]) -> u32 {
+    collecs
+        .iter()
+        .filter(|collec| collec.parentId == *parent_id)
+        .map(|collec| collec.order + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Removes repeated game ids while keeping their order.
+fn dedup_ids(ids: Vec<String>) -> Vec<String> {
+    let mut seen: HashSet<String> = HashSet::new();
+    ids.into_iter().filter(|id| seen.insert(id.clone())).collect()
+}
+
+/// Checks that a smart collection rule can be parsed. Empty rules are treated as no rule.
+fn validate_rule(rule: Option<String>) -> Result<Option<String>> {
+    match rule {
+        Some(rule) if !rule.trim().is_empty() => {
+            Rule::parse(&rule)?;
+            Ok(Some(rule.trim().to_string()))
+        }
+        _ => Ok(None),
+    }
 }
 
 /// Returns index of MonarchCollection with matching id.
@@ -1,4 +1,9 @@
+use super::collection_rules::RuleError;
 use super::collections;
+use super::duplicates::{self, DuplicateGroup, MergedGame};
+use super::search;
+use super::user_data;
+use crate::monarch_games::monarchgame::MonarchGame;
 use core::result::Result; // Using different Result type for sending to frontend.
 use serde_json::Value;
 use tracing::error;
@@ -8,14 +13,19 @@ use tracing::error;
 pub async fn create_collection(
     collection_name: String,
     game_ids: Vec<String>,
+    rule: Option<String>,
+    parent_id: Option<String>,
 ) -> Result<Value, String> {
-    match collections::new_collection(collection_name, game_ids) {
+    match collections::new_collection(collection_name, game_ids, rule, parent_id) {
         Ok(result) => Ok(result),
         Err(e) => {
             error!(
                 "monarch_library::commands::create_collection() -> {}",
                 e.chain().map(|e| e.to_string()).collect::<String>()
             );
+            if let Some(rule_error) = e.downcast_ref::<RuleError>() {
+                return Err(format!("Invalid collection rule: {rule_error}"));
+            }
             Err(String::from(
                 "Something went wrong while creating a new collection!",
             ))
@@ -29,14 +39,18 @@ pub async fn update_collection(
     id: String,
     new_name: String,
     game_ids: Vec<String>,
+    rule: Option<String>,
 ) -> Result<Value, String> {
-    match collections::update_collections(&id, &new_name, game_ids) {
+    match collections::update_collections(&id, &new_name, game_ids, rule) {
         Ok(result) => Ok(result),
         Err(e) => {
             error!(
                 "monarch_library::commands::update_collection() -> {}",
                 e.chain().map(|e| e.to_string()).collect::<String>()
             );
+            if let Some(rule_error) = e.downcast_ref::<RuleError>() {
+                return Err(format!("Invalid collection rule: {rule_error}"));
+            }
             Err(String::from(
                 "Something went wrong while updating collection!",
             ))
@@ -61,6 +75,57 @@ pub async fn delete_collection(id: String) -> Result<Value, String> {
     }
 }
 
+#[tauri::command]
+/// Sets the order of collections, ids are given in their new order
+pub async fn reorder_collections(ids: Vec<String>) -> Result<Value, String> {
+    match collections::reorder_collections(ids) {
+        Ok(result) => Ok(result),
+        Err(e) => {
+            error!(
+                "monarch_library::commands::reorder_collections() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            Err(String::from(
+                "Something went wrong while reordering collections!",
+            ))
+        }
+    }
+}
+
+#[tauri::command]
+/// Moves a collection inside another one, or to the top level without a parent
+pub async fn move_collection(id: String, parent_id: Option<String>) -> Result<Value, String> {
+    match collections::move_collection(&id, parent_id) {
+        Ok(result) => Ok(result),
+        Err(e) => {
+            error!(
+                "monarch_library::commands::move_collection() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            Err(String::from(
+                "Something went wrong while moving collection!",
+            ))
+        }
+    }
+}
+
+#[tauri::command]
+/// Sets the order of games in a collection
+pub async fn reorder_collection_games(id: String, game_ids: Vec<String>) -> Result<Value, String> {
+    match collections::reorder_collection_games(&id, game_ids) {
+        Ok(result) => Ok(result),
+        Err(e) => {
+            error!(
+                "monarch_library::commands::reorder_collection_games() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            Err(String::from(
+                "Something went wrong while reordering games in collection!",
+            ))
+        }
+    }
+}
+
 #[tauri::command]
 /// Reads collections from JSON
 pub async fn get_collections() -> Result<Value, String> {
@@ -77,3 +142,125 @@ pub async fn get_collections() -> Result<Value, String> {
         }
     }
 }
+
+#[tauri::command]
+/// Fuzzy searches the local library, best matches and most played games first
+pub async fn search_library(query: String, limit: usize) -> Vec<MonarchGame> {
+    search::search_library(&query, limit)
+}
+
+#[tauri::command]
+/// Adds tags to several games at once
+pub async fn add_tags(game_ids: Vec<String>, tags: Vec<String>) -> Result<(), String> {
+    if let Err(e) = user_data::add_tags(&game_ids, &tags) {
+        error!(
+            "monarch_library::commands::add_tags() -> {}",
+            e.chain().map(|e| e.to_string()).collect::<String>()
+        );
+        return Err(String::from("Something went wrong while tagging games!"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+/// Removes tags from several games at once
+pub async fn remove_tags(game_ids: Vec<String>, tags: Vec<String>) -> Result<(), String> {
+    if let Err(e) = user_data::remove_tags(&game_ids, &tags) {
+        error!(
+            "monarch_library::commands::remove_tags() -> {}",
+            e.chain().map(|e| e.to_string()).collect::<String>()
+        );
+        return Err(String::from("Something went wrong while removing tags!"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+/// Returns every tag used in the library
+pub async fn get_tags() -> Vec<String> {
+    user_data::get_tags()
+}
+
+#[tauri::command]
+/// Marks or unmarks several games as favorites
+pub async fn set_favorite(game_ids: Vec<String>, favorite: bool) -> Result<(), String> {
+    if let Err(e) = user_data::set_favorite(&game_ids, favorite) {
+        error!(
+            "monarch_library::commands::set_favorite() -> {}",
+            e.chain().map(|e| e.to_string()).collect::<String>()
+        );
+        return Err(String::from("Something went wrong while updating favorites!"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+/// Hides or unhides several games
+pub async fn set_hidden(game_ids: Vec<String>, hidden: bool) -> Result<(), String> {
+    if let Err(e) = user_data::set_hidden(&game_ids, hidden) {
+        error!(
+            "monarch_library::commands::set_hidden() -> {}",
+            e.chain().map(|e| e.to_string()).collect::<String>()
+        );
+        return Err(String::from("Something went wrong while hiding games!"));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+/// Returns groups of library entries that look like the same game
+pub async fn find_duplicate_games() -> Result<Vec<DuplicateGroup>, String> {
+    match duplicates::find_duplicates() {
+        Ok(groups) => Ok(groups),
+        Err(e) => {
+            error!(
+                "monarch_library::commands::find_duplicate_games() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            Err(String::from("Something went wrong while looking for duplicates!"))
+        }
+    }
+}
+
+#[tauri::command]
+/// Merges library entries into one game, launched through the preferred entry
+pub async fn merge_games(game_ids: Vec<String>, preferred: String) -> Result<MergedGame, String> {
+    match duplicates::merge_games(game_ids, &preferred) {
+        Ok(merge) => Ok(merge),
+        Err(e) => {
+            error!(
+                "monarch_library::commands::merge_games() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            Err(String::from("Something went wrong while merging games!"))
+        }
+    }
+}
+
+#[tauri::command]
+/// Sets which installation a merged game is launched through
+pub async fn set_preferred_installation(game_id: String) -> Result<MergedGame, String> {
+    match duplicates::set_preferred(&game_id) {
+        Ok(merge) => Ok(merge),
+        Err(e) => {
+            error!(
+                "monarch_library::commands::set_preferred_installation() -> {}",
+                e.chain().map(|e| e.to_string()).collect::<String>()
+            );
+            Err(String::from("Something went wrong while changing preferred installation!"))
+        }
+    }
+}
+
+#[tauri::command]
+/// Splits a merged game back into seperate entries
+pub async fn unmerge_games(game_id: String) -> Result<(), String> {
+    if let Err(e) = duplicates::unmerge(&game_id) {
+        error!(
+            "monarch_library::commands::unmerge_games() -> {}",
+            e.chain().map(|e| e.to_string()).collect::<String>()
+        );
+        return Err(String::from("Something went wrong while unmerging games!"));
+    }
+    Ok(())
+}
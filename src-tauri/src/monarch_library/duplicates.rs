@@ -0,0 +1,379 @@
+/*
+* Detection and merging of the same game owned through several sources.
+*
+* Entries are considered duplicates if they share an IGDB id, a store page or a normalized
+* name. Merging is kept in merges.json and never touches library.json, so a merge can always
+* be undone. A merged game shows up as its preferred entry, with every entry as an installation.
+*/
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use tracing::error;
+
+use super::search::{mark_index_outdated, normalize_name};
+use crate::monarch_games::monarchgame::{Installation, MonarchGame};
+use crate::monarch_utils::monarch_fs::{get_merges_json_path, path_exists, write_json_content};
+use crate::monarch_utils::monarch_state::MONARCH_STATE;
+
+/// Several library entries the user has merged into one game.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MergedGame {
+    pub game_ids: Vec<String>,
+    pub preferred: String, // Id of the entry used to launch the game
+}
+
+/// Entries that look like the same game, as suggested to the user.
+#[derive(Serialize, Clone, Debug)]
+pub struct DuplicateGroup {
+    pub games: Vec<MonarchGame>,
+    pub reasons: Vec<String>,
+}
+
+/// Returns groups of library entries that are probably the same game.
+/// Groups that have already been merged are left out.
+pub fn find_duplicates() -> Result<Vec<DuplicateGroup>> {
+    let games: Vec<MonarchGame>;
+    unsafe {
+        games = MONARCH_STATE.get_library_games();
+    }
+    let merges: Vec<MergedGame> =
+        get_merges().with_context(|| "duplicates::find_duplicates() -> ")?;
+
+    let mut groups: DisjointSet = DisjointSet::new(games.len());
+    let mut seen_keys: HashMap<(&str, String), usize> = HashMap::new();
+
+    for (i, game) in games.iter().enumerate() {
+        for (reason, key) in duplicate_keys(game) {
+            match seen_keys.get(&(reason, key.clone())) {
+                Some(&first) if first != i => groups.union(first, i, reason),
+                Some(_) => {}
+                None => {
+                    seen_keys.insert((reason, key), i);
+                }
+            }
+        }
+    }
+
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..games.len() {
+        members.entry(groups.find(i)).or_default().push(i);
+    }
+
+    let mut duplicates: Vec<DuplicateGroup> = members
+        .into_iter()
+        .filter(|(_, indices)| indices.len() > 1)
+        .filter(|(_, indices)| {
+            // Already merged if one merge contains every entry of the group
+            !merges.iter().any(|merge| {
+                indices
+                    .iter()
+                    .all(|&i| merge.game_ids.contains(&games[i].id))
+            })
+        })
+        .map(|(root, indices)| DuplicateGroup {
+            games: indices.iter().map(|&i| games[i].clone()).collect(),
+            reasons: groups.reasons(root),
+        })
+        .collect();
+
+    duplicates.sort_by(|a, b| a.games[0].name.cmp(&b.games[0].name));
+    Ok(duplicates)
+}
+
+/// Merges library entries into one game, launched through the preferred entry.
+pub fn merge_games(game_ids: Vec<String>, preferred: &str) -> Result<MergedGame> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut game_ids: Vec<String> = game_ids;
+    game_ids.retain(|id| seen.insert(id.clone()));
+
+    if game_ids.len() < 2 {
+        bail!("duplicates::merge_games() | Err: At least two games are needed for a merge!")
+    }
+    if !game_ids.iter().any(|id| id == preferred) {
+        bail!("duplicates::merge_games() | Err: Preferred game: {preferred} is not part of the merge!")
+    }
+
+    let games: Vec<MonarchGame>;
+    unsafe {
+        games = MONARCH_STATE.get_library_games();
+    }
+    if let Some(id) = game_ids
+        .iter()
+        .find(|id| !games.iter().any(|game| game.id == **id))
+    {
+        bail!("duplicates::merge_games() | Err: No game in library with id: {id}")
+    }
+
+    let mut merges: Vec<MergedGame> =
+        get_merges().with_context(|| "duplicates::merge_games() -> ")?;
+
+    // Entries can only be part of one merge, existing merges are combined with the new one
+    let mut combined: Vec<String> = Vec::new();
+    merges.retain(|merge| {
+        if merge.game_ids.iter().any(|id| game_ids.contains(id)) {
+            combined.extend(merge.game_ids.iter().cloned());
+            return false;
+        }
+        true
+    });
+    for id in combined {
+        if !game_ids.contains(&id) {
+            game_ids.push(id);
+        }
+    }
+
+    let merge: MergedGame = MergedGame {
+        game_ids,
+        preferred: preferred.to_string(),
+    };
+    merges.push(merge.clone());
+
+    write_merges(&merges).with_context(|| "duplicates::merge_games() -> ")?;
+    Ok(merge)
+}
+
+/// Changes which entry a merged game is launched through.
+pub fn set_preferred(game_id: &str) -> Result<MergedGame> {
+    let mut merges: Vec<MergedGame> =
+        get_merges().with_context(|| "duplicates::set_preferred() -> ")?;
+
+    let merge: &mut MergedGame = match merges
+        .iter_mut()
+        .find(|merge| merge.game_ids.iter().any(|id| id == game_id))
+    {
+        Some(merge) => merge,
+        None => bail!("duplicates::set_preferred() | Err: Game: {game_id} is not part of a merge!"),
+    };
+    merge.preferred = game_id.to_string();
+    let merge: MergedGame = merge.clone();
+
+    write_merges(&merges).with_context(|| "duplicates::set_preferred() -> ")?;
+    Ok(merge)
+}
+
+/// Undoes the merge a game is part of, its entries show up as seperate games again.
+pub fn unmerge(game_id: &str) -> Result<()> {
+    let mut merges: Vec<MergedGame> = get_merges().with_context(|| "duplicates::unmerge() -> ")?;
+
+    let len: usize = merges.len();
+    merges.retain(|merge| !merge.game_ids.iter().any(|id| id == game_id));
+    if merges.len() == len {
+        bail!("duplicates::unmerge() | Err: Game: {game_id} is not part of a merge!")
+    }
+
+    write_merges(&merges).with_context(|| "duplicates::unmerge() -> ")
+}
+
+/// Replaces the entries of merged games with a single entry, which lists every entry as an
+/// installation. If the preferred entry is missing, the first entry still in games is used.
+pub fn apply_merges(games: &mut Vec<MonarchGame>) {
+    let merges: Vec<MergedGame> = match get_merges() {
+        Ok(merges) => merges,
+        Err(e) => {
+            error!("duplicates::apply_merges() -> {e}");
+            return;
+        }
+    };
+
+    for merge in merges {
+        let installations: Vec<Installation> = merge
+            .game_ids
+            .iter()
+            .filter_map(|id| games.iter().find(|game| game.id == *id))
+            .map(Installation::from)
+            .collect();
+
+        if installations.len() < 2 {
+            continue;
+        }
+
+        let shown: &str = if installations.iter().any(|i| i.id == merge.preferred) {
+            &merge.preferred
+        } else {
+            &installations[0].id
+        };
+        let shown: String = shown.to_string();
+
+        games.retain(|game| game.id == shown || !merge.game_ids.contains(&game.id));
+        if let Some(game) = games.iter_mut().find(|game| game.id == shown) {
+            game.installations = installations;
+        }
+    }
+}
+
+/// Returns every merge the user has made.
+pub fn get_merges() -> Result<Vec<MergedGame>> {
+    let path: PathBuf = get_merges_json_path();
+    if !path_exists(&path) {
+        return Ok(Vec::new());
+    }
+
+    let content: String = fs::read_to_string(&path).with_context(|| {
+        format!(
+            "duplicates::get_merges() Error reading: {file} | Err: ",
+            file = path.display()
+        )
+    })?;
+    serde_json::from_str(&content)
+        .with_context(|| "duplicates::get_merges() Failed to parse merges! | Err: ")
+}
+
+fn write_merges(merges: &[MergedGame]) -> Result<()> {
+    let path: PathBuf = get_merges_json_path();
+    write_json_content(json!(merges), &path).with_context(|| "duplicates::write_merges() -> ")?;
+
+    mark_index_outdated();
+    Ok(())
+}
+
+/// Keys that are equal for entries of the same game, along with the reason shown to the user.
+/// Each key is given once, even if several fields of the game lead to it.
+fn duplicate_keys(game: &MonarchGame) -> BTreeSet<(&'static str, String)> {
+    let mut keys: BTreeSet<(&'static str, String)> = BTreeSet::new();
+
+    if let Some(igdb_id) = game.igdb_id {
+        keys.insert(("Same IGDB entry", igdb_id.to_string()));
+    }
+
+    let name: String = normalize_name(&game.name);
+    if !name.is_empty() {
+        keys.insert(("Same name", name));
+    }
+
+    if let Some(store_page) = normalize_store_page(&game.store_page) {
+        keys.insert(("Same store page", store_page));
+    }
+
+    // Steam and SteamCMD install the same games
+    if matches!(game.platform.as_str(), "steam" | "steamcmd") && !game.platform_id.is_empty() {
+        keys.insert(("Same store page", format!("steam:{}", game.platform_id)));
+    }
+
+    keys
+}
+
+/// Strips parts of a store link that differ between otherwise equal links.
+/// Steam links are cut after the app id, as the rest is just the game name.
+fn normalize_store_page(url: &str) -> Option<String> {
+    let url: String = url.trim().to_lowercase();
+    let url: &str = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("www.");
+    let url: &str = url.split(['?', '#']).next().unwrap_or_default();
+    let url: &str = url.trim_end_matches('/');
+
+    if url.is_empty() || !url.contains('/') {
+        return None;
+    }
+
+    if let Some(path) = url.strip_prefix("store.steampowered.com/app/") {
+        let app_id: &str = path.split('/').next().unwrap_or_default();
+        return Some(format!("steam:{app_id}"));
+    }
+    Some(url.to_string())
+}
+
+/// Union-find over indices into the library, remembering why entries were grouped.
+struct DisjointSet {
+    parents: Vec<usize>,
+    reasons: HashMap<usize, BTreeSet<&'static str>>,
+}
+
+impl DisjointSet {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+            reasons: HashMap::new(),
+        }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root: usize = i;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+
+        // Point everything on the way directly to the root
+        let mut current: usize = i;
+        while self.parents[current] != root {
+            let next: usize = self.parents[current];
+            self.parents[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize, reason: &'static str) {
+        // An entry matching itself is no reason for its group
+        if a == b {
+            return;
+        }
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parents[b] = a;
+            let moved: BTreeSet<&'static str> = self.reasons.remove(&b).unwrap_or_default();
+            self.reasons.entry(a).or_default().extend(moved);
+        }
+        self.reasons.entry(a).or_default().insert(reason);
+    }
+
+    fn reasons(&self, root: usize) -> Vec<String> {
+        self.reasons
+            .get(&root)
+            .map(|reasons| reasons.iter().map(|r| r.to_string()).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(name: &str, platform: &str, platform_id: &str, store_page: &str) -> MonarchGame {
+        MonarchGame {
+            name: name.to_string(),
+            platform: platform.to_string(),
+            platform_id: platform_id.to_string(),
+            store_page: store_page.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn duplicate_keys_are_given_once() {
+        let portal: MonarchGame = game(
+            "Portal",
+            "steam",
+            "400",
+            "https://store.steampowered.com/app/400/Portal/",
+        );
+        let keys: BTreeSet<(&str, String)> = duplicate_keys(&portal);
+
+        assert_eq!(
+            keys.into_iter().collect::<Vec<_>>(),
+            [
+                ("Same name", String::from("portal")),
+                ("Same store page", String::from("steam:400")),
+            ]
+        );
+    }
+
+    #[test]
+    fn union_only_gives_reasons_for_separate_entries() {
+        let mut groups: DisjointSet = DisjointSet::new(3);
+        groups.union(0, 0, "Same store page");
+        groups.union(0, 1, "Same name");
+        groups.union(1, 0, "Same IGDB entry");
+
+        let root: usize = groups.find(0);
+        assert_eq!(groups.find(1), root);
+        assert_eq!(groups.reasons(root), ["Same IGDB entry", "Same name"]);
+        let alone: usize = groups.find(2);
+        assert!(groups.reasons(alone).is_empty());
+    }
+}
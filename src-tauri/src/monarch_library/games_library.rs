@@ -1,9 +1,13 @@
 use anyhow::{Context, Result};
 use serde_json::{json, value::Value};
+use std::collections::BTreeMap;
 use std::fs;
 use std::fs::File;
 use std::path::PathBuf;
 
+use super::collections::remove_game_from_collections;
+use super::duplicates::apply_merges;
+use super::user_data::apply_user_data;
 use crate::monarch_games::monarchgame::MonarchGame;
 use crate::monarch_utils::monarch_fs::{
     get_library_json_path, get_monarch_games_path, path_exists, write_json_content,
@@ -69,6 +73,23 @@ pub fn get_games() -> Result<Value> {
     Ok(games) // Seperate return statement for verbosity
 }
 
+/// Returns games from library with tags, favorites, hidden and merged games applied.
+/// Hidden games are left out unless include_hidden is set.
+pub fn get_library(include_hidden: bool) -> Result<Vec<MonarchGame>> {
+    let games_json: Value = get_games().with_context(|| "games_library::get_library() -> ")?;
+
+    let mut games: Vec<MonarchGame> = serde_json::from_value(games_json).with_context(|| {
+        "games_library::get_library() Failed to parse json to Vec<MonarchGame>! | Err: "
+    })?;
+    apply_user_data(&mut games);
+    apply_merges(&mut games);
+
+    if !include_hidden {
+        games.retain(|game| !game.hidden);
+    }
+    Ok(games)
+}
+
 /// Returns Vec of games installed by Monarch
 pub fn get_monarchgames() -> Result<Vec<MonarchGame>> {
     let path: PathBuf = get_monarch_games_path();
@@ -115,6 +136,8 @@ pub fn remove_game(game: &MonarchGame) -> Result<()> {
         MONARCH_STATE.set_library_games(&games);
     }
 
+    remove_game_from_collections(&game.id).with_context(|| "games_library::remove_game() -> ")?;
+
     let mut monarch_games = get_monarchgames().with_context(|| "games_library::remove_game() -> ")?;
     for (i, g) in monarch_games.iter_mut().enumerate() {
         if g.id == game.id {
@@ -125,8 +148,43 @@ pub fn remove_game(game: &MonarchGame) -> Result<()> {
     write_monarch_games(&monarch_games)
 }
 
+/// Sets which games have an update available, by game id, in the library and in
+/// monarch_games.json.
+pub fn set_updates_available(updates: &BTreeMap<String, bool>) -> Result<()> {
+    edit_games(|game| {
+        if let Some(update_available) = updates.get(&game.id) {
+            game.update_available = *update_available;
+        }
+    })
+    .with_context(|| "games_library::set_updates_available() -> ")
+}
+
+/// Applies an edit to every game, in the library and in monarch_games.json.
+pub fn edit_games(edit: impl Fn(&mut MonarchGame)) -> Result<()> {
+    unsafe {
+        let mut games: Vec<MonarchGame> = MONARCH_STATE.get_library_games();
+        games.iter_mut().for_each(&edit);
+        MONARCH_STATE.set_library_games(&games);
+    }
+
+    let games_json: Value = get_games().with_context(|| "games_library::edit_games() -> ")?;
+    let mut games: Vec<MonarchGame> = serde_json::from_value(games_json).with_context(|| {
+        "games_library::edit_games() Failed to parse json to Vec<MonarchGame>! | Err: "
+    })?;
+    games.iter_mut().for_each(&edit);
+    write_games(&games).with_context(|| "games_library::edit_games() -> ")?;
+
+    let mut monarch_games: Vec<MonarchGame> =
+        get_monarchgames().with_context(|| "games_library::edit_games() -> ")?;
+    monarch_games.iter_mut().for_each(&edit);
+    write_monarch_games(&monarch_games)
+}
+
 /// Updates the properties of a game in the library.
 pub fn update_game_properties(game: &MonarchGame) -> Result<()> {
+    game.validate_profiles()
+        .with_context(|| "games_library::update_game_properties() -> ")?;
+
     let games_json: Value =
         get_games().with_context(|| "games_library::update_game_properties() -> ")?;
 
@@ -139,6 +197,13 @@ pub fn update_game_properties(game: &MonarchGame) -> Result<()> {
             library_game.compatibility = game.compatibility.to_string();
             library_game.launch_args = game.launch_args.to_string();
             library_game.executable_path = game.executable_path.to_string();
+            library_game.launch_profiles = game.launch_profiles.clone();
+            library_game.default_profile = game.default_profile.to_string();
+            library_game.wrappers = game.wrappers.clone();
+            library_game.working_dir = game.working_dir.to_string();
+            library_game.env = game.env.clone();
+            library_game.hooks = game.hooks.clone();
+            library_game.show_terminal = game.show_terminal;
             break;
         }
     }
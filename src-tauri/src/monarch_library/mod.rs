@@ -1,3 +1,9 @@
+pub mod collection_rules;
 pub mod collections;
 pub mod commands;
+pub mod duplicates;
 pub mod games_library;
+pub mod play_history;
+pub mod recommendations;
+pub mod search;
+pub mod user_data;
@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, warn};
+
+use crate::monarch_utils::monarch_fs::{
+    backup_file, get_play_history_json_path, path_exists, write_json_content,
+};
+
+/// Amount of launch timestamps to keep per game.
+const MAX_LAUNCHES: usize = 50;
+
+/// Launches lose half their weight in frecency after this many days.
+const FRECENCY_HALF_LIFE_DAYS: f64 = 14.0;
+
+// Play history is read from disk once and kept in memory after that.
+static PLAY_HISTORY: Lazy<Mutex<Option<HashMap<String, GameHistory>>>> =
+    Lazy::new(|| Mutex::new(None));
+
+/// What Monarch knows about when a game has been played.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GameHistory {
+    pub launch_count: u64,
+    pub last_played: u64,
+    pub launches: Vec<u64>, // Unix timestamps of the most recent launches
+    #[serde(default)]
+    pub sessions: u64,
+    #[serde(default)]
+    pub playtime: u64, // Seconds played in total
+}
+
+impl GameHistory {
+    /// Score for how often and how recently a game has been launched.
+    pub fn frecency(&self, now: u64) -> f64 {
+        self.launches
+            .iter()
+            .map(|launch| {
+                let age_days: f64 = now.saturating_sub(*launch) as f64 / 86400.0;
+                0.5_f64.powf(age_days / FRECENCY_HALF_LIFE_DAYS)
+            })
+            .sum()
+    }
+}
+
+/// Records that a game was just launched.
+pub fn record_launch(id: &str) {
+    let now: u64 = unix_now();
+    let mut lock = lock_history();
+    let history = loaded_history(&mut lock);
+
+    let game: &mut GameHistory = history.entry(id.to_string()).or_default();
+    game.launch_count += 1;
+    game.last_played = now;
+    game.launches.push(now);
+    if game.launches.len() > MAX_LAUNCHES {
+        let excess: usize = game.launches.len() - MAX_LAUNCHES;
+        game.launches.drain(0..excess);
+    }
+
+    if let Err(e) = write_history(history) {
+        error!("play_history::record_launch() -> {e}");
+    }
+}
+
+/// Records that a game was played for a number of seconds.
+pub fn record_session(id: &str, seconds: u64) {
+    let mut lock = lock_history();
+    let history = loaded_history(&mut lock);
+
+    let game: &mut GameHistory = history.entry(id.to_string()).or_default();
+    game.sessions += 1;
+    game.playtime += seconds;
+
+    if let Err(e) = write_history(history) {
+        error!("play_history::record_session() -> {e}");
+    }
+}
+
+/// Returns play history of all games.
+pub fn get_history() -> HashMap<String, GameHistory> {
+    let mut lock = lock_history();
+    loaded_history(&mut lock).clone()
+}
+
+/// Returns current time as a unix timestamp.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Returns the in memory history, reading it from disk if it hasn't been already.
+fn loaded_history<'a>(
+    lock: &'a mut MutexGuard<'static, Option<HashMap<String, GameHistory>>>,
+) -> &'a mut HashMap<String, GameHistory> {
+    lock.get_or_insert_with(|| match read_history() {
+        Ok(history) => history,
+        Err(e) => {
+            error!("play_history::loaded_history() -> {e:#}");
+
+            // Starting fresh would overwrite the history on the next launch, so it's kept aside
+            match backup_file(&get_play_history_json_path()) {
+                Ok(backup) => warn!("Unreadable play history moved to: {}", backup.display()),
+                Err(e) => error!("play_history::loaded_history() -> {e:#}"),
+            }
+            HashMap::new()
+        }
+    })
+}
+
+fn lock_history() -> MutexGuard<'static, Option<HashMap<String, GameHistory>>> {
+    PLAY_HISTORY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Reads play_history.json, an empty history is returned if the file doesn't exist yet.
+fn read_history() -> Result<HashMap<String, GameHistory>> {
+    let path: PathBuf = get_play_history_json_path();
+    if !path_exists(&path) {
+        return Ok(HashMap::new());
+    }
+
+    let content: String = fs::read_to_string(&path).with_context(|| {
+        format!(
+            "play_history::read_history() Error reading: {file} | Err: ",
+            file = path.display()
+        )
+    })?;
+    serde_json::from_str(&content)
+        .with_context(|| "play_history::read_history() Failed to parse play history! | Err: ")
+}
+
+fn write_history(history: &HashMap<String, GameHistory>) -> Result<()> {
+    let path: PathBuf = get_play_history_json_path();
+    write_json_content(json!(history), &path).with_context(|| "play_history::write_history() -> ")
+}
@@ -0,0 +1,203 @@
+/*
+* Recommendations shown on the home page.
+*
+* Games are picked from play history and user data into sections, each explaining why its
+* games are there. Randomness is seeded by the current day, so the home page stays the same
+* throughout a day and changes the next.
+*/
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use super::play_history::{self, GameHistory};
+use crate::monarch_games::monarchgame::MonarchGame;
+use crate::monarch_utils::monarch_settings::{get_settings_state, HomeSettings};
+
+const DAY: u64 = 86400;
+
+/// Games played within this many days count as being played currently.
+const CONTINUE_PLAYING_DAYS: u64 = 14;
+
+/// Favorites not played for this many days count as forgotten.
+const FORGOTTEN_DAYS: u64 = 30;
+
+/// Games played for this many minutes per session, or less, count as quick.
+const QUICK_SESSION_MINUTES: u64 = 45;
+
+/// A section of recommendations, as sent to the frontend.
+#[derive(Serialize, Clone, Debug)]
+pub struct RecommendationSection {
+    pub id: String,
+    pub title: String,
+    pub explanation: String,
+    pub games: Vec<MonarchGame>,
+}
+
+/// Builds the sections enabled in settings from the games given.
+/// A game is only recommended once, in the first section it fits in.
+pub fn get_recommendations(games: &[MonarchGame]) -> Vec<RecommendationSection> {
+    let settings: HomeSettings = get_settings_state().home;
+    let history: HashMap<String, GameHistory> = play_history::get_history();
+    let now: u64 = play_history::unix_now();
+    let day: u64 = now / DAY;
+
+    let mut shown: HashSet<String> = HashSet::new();
+    let mut sections: Vec<RecommendationSection> = Vec::new();
+
+    let builders: [(bool, SectionBuilder); 4] = [
+        (settings.continue_playing, continue_playing),
+        (settings.installed_never_launched, installed_never_launched),
+        (settings.forgotten_favorites, forgotten_favorites),
+        (settings.quick_session, quick_session),
+    ];
+
+    for (enabled, builder) in builders {
+        if !enabled {
+            continue;
+        }
+
+        let section: Section = builder(games, &history, now);
+        let mut candidates: Vec<&MonarchGame> = section
+            .games
+            .into_iter()
+            .filter(|game| !shown.contains(&game.id))
+            .collect();
+
+        if section.shuffle {
+            let mut rng: StdRng = StdRng::seed_from_u64(day_seed(day, section.id));
+            candidates.shuffle(&mut rng);
+        }
+        candidates.truncate(settings.games_per_section);
+
+        if candidates.is_empty() {
+            continue;
+        }
+
+        shown.extend(candidates.iter().map(|game| game.id.clone()));
+        sections.push(RecommendationSection {
+            id: section.id.to_string(),
+            title: section.title.to_string(),
+            explanation: section.explanation,
+            games: candidates.into_iter().cloned().collect(),
+        });
+    }
+
+    sections
+}
+
+type SectionBuilder =
+    for<'a> fn(&'a [MonarchGame], &HashMap<String, GameHistory>, u64) -> Section<'a>;
+
+/// Candidates for a section, before being limited to the amount of games shown.
+struct Section<'a> {
+    id: &'static str,
+    title: &'static str,
+    explanation: String,
+    games: Vec<&'a MonarchGame>,
+    shuffle: bool, // Sections without a meaningful order are shuffled once a day
+}
+
+/// Games with recent sessions, most recent first.
+fn continue_playing<'a>(
+    games: &'a [MonarchGame],
+    history: &HashMap<String, GameHistory>,
+    now: u64,
+) -> Section<'a> {
+    let mut recent: Vec<(&MonarchGame, u64)> = games
+        .iter()
+        .filter_map(|game| {
+            let last_played: u64 = history.get(&game.id)?.last_played;
+            (last_played > 0 && now.saturating_sub(last_played) <= CONTINUE_PLAYING_DAYS * DAY)
+                .then_some((game, last_played))
+        })
+        .collect();
+    recent.sort_by(|(a, a_played), (b, b_played)| {
+        b_played.cmp(a_played).then_with(|| a.name.cmp(&b.name))
+    });
+
+    Section {
+        id: "continue_playing",
+        title: "Continue playing",
+        explanation: format!("Games you have played in the last {CONTINUE_PLAYING_DAYS} days."),
+        games: recent.into_iter().map(|(game, _)| game).collect(),
+        shuffle: false,
+    }
+}
+
+/// Games in the library that have never been launched through Monarch.
+fn installed_never_launched<'a>(
+    games: &'a [MonarchGame],
+    history: &HashMap<String, GameHistory>,
+    _now: u64,
+) -> Section<'a> {
+    Section {
+        id: "installed_never_launched",
+        title: "Installed but never launched",
+        explanation: String::from("Games you have installed but haven't played yet."),
+        games: games
+            .iter()
+            .filter(|game| history.get(&game.id).is_none_or(|h| h.launch_count == 0))
+            .collect(),
+        shuffle: true,
+    }
+}
+
+/// Favorites that haven't been played for a while.
+fn forgotten_favorites<'a>(
+    games: &'a [MonarchGame],
+    history: &HashMap<String, GameHistory>,
+    now: u64,
+) -> Section<'a> {
+    Section {
+        id: "forgotten_favorites",
+        title: "Forgotten favorites",
+        explanation: format!("Favorites you haven't played in over {FORGOTTEN_DAYS} days."),
+        games: games
+            .iter()
+            .filter(|game| game.favorite)
+            .filter(|game| {
+                history
+                    .get(&game.id)
+                    .is_none_or(|h| now.saturating_sub(h.last_played) > FORGOTTEN_DAYS * DAY)
+            })
+            .collect(),
+        shuffle: true,
+    }
+}
+
+/// Games usually played in short sessions.
+fn quick_session<'a>(
+    games: &'a [MonarchGame],
+    history: &HashMap<String, GameHistory>,
+    _now: u64,
+) -> Section<'a> {
+    Section {
+        id: "quick_session",
+        title: "Quick session",
+        explanation: format!(
+            "Games you usually play for {QUICK_SESSION_MINUTES} minutes or less at a time."
+        ),
+        games: games
+            .iter()
+            .filter(|game| {
+                history.get(&game.id).is_some_and(|h| {
+                    h.sessions > 0 && h.playtime / h.sessions <= QUICK_SESSION_MINUTES * 60
+                })
+            })
+            .collect(),
+        shuffle: true,
+    }
+}
+
+/// Seed that is the same for a section throughout a day.
+fn day_seed(day: u64, section: &str) -> u64 {
+    // FNV-1a, std's hashers aren't guaranteed to be stable
+    section
+        .bytes()
+        .fold(0xcbf29ce484222325 ^ day, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
@@ -0,0 +1,431 @@
+/*
+* Fuzzy search of the local library, used by quicklaunch.
+*
+* Game names are normalized once into an in memory index, which is rebuilt on the next search
+* after the library has changed. Matching is forgiving about typos, word prefixes, acronyms
+* ("gta5"), roman numerals and diacritics. Results are ranked by how well they match, combined
+* with how often and how recently the game has been launched.
+*/
+
+use once_cell::sync::Lazy;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::RwLock;
+
+use super::duplicates::apply_merges;
+use super::play_history::{self, GameHistory};
+use crate::monarch_games::monarchgame::MonarchGame;
+use crate::monarch_utils::monarch_state::MONARCH_STATE;
+
+/// How much frecency can boost the match score of a game.
+const FRECENCY_WEIGHT: f64 = 0.3;
+
+/// Results with a lower match score than this are not returned.
+const MIN_SCORE: f64 = 0.3;
+
+static SEARCH_INDEX: Lazy<RwLock<Vec<IndexedGame>>> = Lazy::new(|| RwLock::new(Vec::new()));
+static INDEX_OUTDATED: AtomicBool = AtomicBool::new(true);
+
+/// A word of a game name, with its numeric value if it is a roman numeral or vice versa.
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    alt: Option<String>,
+}
+
+impl Token {
+    fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            alt: roman_to_arabic(text).map(|n| n.to_string()),
+        }
+    }
+
+    fn variants(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.text.as_str()).chain(self.alt.as_deref())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct IndexedGame {
+    game: MonarchGame,
+    tokens: Vec<Token>,
+    compact: String,
+    acronyms: Vec<String>,
+}
+
+impl IndexedGame {
+    fn new(game: &MonarchGame) -> Self {
+        let tokens: Vec<Token> = tokenize(&game.name);
+        let compact: String = tokens.iter().map(|t| t.text.as_str()).collect();
+
+        // "Grand Theft Auto V" gives both "gtav" and "gta5"
+        let mut acronym: String = String::new();
+        let mut numeric_acronym: String = String::new();
+        for token in &tokens {
+            if token.text.chars().all(|c| c.is_ascii_digit()) {
+                acronym.push_str(&token.text);
+                numeric_acronym.push_str(&token.text);
+            } else if let Some(number) = &token.alt {
+                acronym.push_str(&token.text);
+                numeric_acronym.push_str(number);
+            } else if let Some(first) = token.text.chars().next() {
+                acronym.push(first);
+                numeric_acronym.push(first);
+            }
+        }
+
+        let mut acronyms: Vec<String> = vec![acronym];
+        if !acronyms.contains(&numeric_acronym) {
+            acronyms.push(numeric_acronym);
+        }
+
+        Self {
+            game: game.clone(),
+            tokens,
+            compact,
+            acronyms,
+        }
+    }
+
+    /// Returns how well a query matches this game, from 0.0 to 1.0.
+    fn match_score(&self, query: &[Token], query_compact: &str) -> f64 {
+        let token_score: f64 = self.token_score(query);
+
+        let acronym_score: f64 = if query_compact.len() < 2 {
+            0.0
+        } else if self.acronyms.iter().any(|a| a == query_compact) {
+            0.95
+        } else if self.acronyms.iter().any(|a| a.starts_with(query_compact)) {
+            0.8
+        } else {
+            0.0
+        };
+
+        let compact_score: f64 = if query_compact.len() < 3 {
+            0.0
+        } else if self.compact.starts_with(query_compact) {
+            0.85
+        } else if self.compact.contains(query_compact) {
+            0.7
+        } else {
+            0.0
+        };
+
+        token_score.max(acronym_score).max(compact_score)
+    }
+
+    /// Every query word has to match a word of the name for a token match.
+    fn token_score(&self, query: &[Token]) -> f64 {
+        if query.is_empty() || self.tokens.is_empty() {
+            return 0.0;
+        }
+
+        let mut total: f64 = 0.0;
+        for query_token in query {
+            let best: f64 = self
+                .tokens
+                .iter()
+                .map(|name_token| token_similarity(query_token, name_token))
+                .fold(0.0, f64::max);
+
+            if best == 0.0 {
+                return 0.0;
+            }
+            total += best;
+        }
+
+        // Prefer names where the query covers more of the name
+        let coverage: f64 = (query.len() as f64 / self.tokens.len() as f64).min(1.0);
+        (total / query.len() as f64) * (0.9 + 0.1 * coverage)
+    }
+}
+
+/// Tells the search index that the library has changed and needs to be reindexed.
+pub fn mark_index_outdated() {
+    INDEX_OUTDATED.store(true, AtomicOrdering::Release);
+}
+
+/// Searches the library for games matching query. An empty query returns the games
+/// played most often and most recently. Hidden games are never returned.
+pub fn search_library(query: &str, limit: usize) -> Vec<MonarchGame> {
+    if INDEX_OUTDATED.swap(false, AtomicOrdering::AcqRel) {
+        rebuild_index();
+    }
+
+    let index = SEARCH_INDEX.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let history: HashMap<String, GameHistory> = play_history::get_history();
+    let now: u64 = play_history::unix_now();
+
+    let query_tokens: Vec<Token> = tokenize(query);
+    let query_compact: String = query_tokens.iter().map(|t| t.text.as_str()).collect();
+
+    let mut results: Vec<(f64, &IndexedGame)> = index
+        .iter()
+        .filter_map(|indexed| {
+            let match_score: f64 = if query_tokens.is_empty() {
+                1.0
+            } else {
+                indexed.match_score(&query_tokens, &query_compact)
+            };
+
+            if match_score < MIN_SCORE {
+                return None;
+            }
+
+            let frecency: f64 = history
+                .get(&indexed.game.id)
+                .map(|h| h.frecency(now))
+                .unwrap_or(0.0);
+            let boost: f64 = FRECENCY_WEIGHT * frecency / (frecency + 1.0);
+
+            Some((match_score * (1.0 + boost), indexed))
+        })
+        .collect();
+
+    results.sort_by(|(a_score, a), (b_score, b)| {
+        b_score
+            .partial_cmp(a_score)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a.game.name.cmp(&b.game.name))
+    });
+
+    results
+        .into_iter()
+        .take(limit)
+        .map(|(_, indexed)| indexed.game.clone())
+        .collect()
+}
+
+/// Reduces a game name to a form where different spellings of the same name are equal,
+/// e.g. "FINAL FANTASY® VII" and "Final Fantasy 7" both become "final fantasy 7".
+pub fn normalize_name(name: &str) -> String {
+    tokenize(name)
+        .iter()
+        .map(|token| token.alt.as_deref().unwrap_or(&token.text))
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn rebuild_index() {
+    let mut games: Vec<MonarchGame>;
+    unsafe {
+        games = MONARCH_STATE.get_library_games();
+    }
+    apply_merges(&mut games);
+
+    let new_index: Vec<IndexedGame> = games
+        .iter()
+        .filter(|game| !game.hidden)
+        .map(IndexedGame::new)
+        .collect();
+    *SEARCH_INDEX.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = new_index;
+}
+
+/// Similarity between a word from the query and a word from a game name.
+fn token_similarity(query: &Token, name: &Token) -> f64 {
+    let mut best: f64 = 0.0;
+
+    for q in query.variants() {
+        for n in name.variants() {
+            best = best.max(word_similarity(q, n));
+        }
+    }
+    best
+}
+
+fn word_similarity(query: &str, word: &str) -> f64 {
+    if query == word {
+        return 1.0;
+    }
+
+    let query_len: usize = query.chars().count();
+    let word_len: usize = word.chars().count();
+
+    if word.starts_with(query) {
+        return 0.75 + 0.15 * (query_len as f64 / word_len as f64);
+    }
+
+    // Allow more typos in longer words
+    let allowed_typos: usize = match query_len {
+        0..=3 => 0,
+        4..=5 => 1,
+        _ => 2,
+    };
+
+    if allowed_typos > 0 {
+        if query_len.abs_diff(word_len) <= allowed_typos
+            && edit_distance(query, word) <= allowed_typos
+        {
+            return 0.7;
+        }
+
+        // Typo in a word that hasn't been fully typed yet
+        let prefix: String = word.chars().take(query_len).collect();
+        if word_len > query_len && edit_distance(query, &prefix) <= 1 {
+            return 0.6;
+        }
+    }
+
+    if query_len >= 3 && word.contains(query) {
+        return 0.5;
+    }
+
+    0.0
+}
+
+/// Optimal string alignment distance, which is Levenshtein distance that also
+/// counts swapping two neighbouring characters as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut distances: Vec<Vec<usize>> = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost: usize = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance: usize = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
+
+/// Splits text into lowercase words without diacritics or special characters.
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut normalized: String = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match fold_diacritic(c) {
+            Some(folded) => normalized.push_str(folded),
+            None if c.is_alphanumeric() => normalized.push(c),
+            None if c == '\'' || c == '’' => {} // "Assassin's" -> "assassins"
+            None => normalized.push(' '),
+        }
+    }
+
+    normalized.split_whitespace().map(Token::new).collect()
+}
+
+/// Replaces common accented latin characters with their plain counterpart.
+fn fold_diacritic(c: char) -> Option<&'static str> {
+    let folded: &str = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ș' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' | 'ț' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    };
+    Some(folded)
+}
+
+/// Converts roman numerals used in game titles (I to XXX) to their value.
+fn roman_to_arabic(word: &str) -> Option<u32> {
+    if word.is_empty() || !word.chars().all(|c| matches!(c, 'i' | 'v' | 'x')) {
+        return None;
+    }
+
+    let value = |c: char| -> i32 {
+        match c {
+            'i' => 1,
+            'v' => 5,
+            _ => 10,
+        }
+    };
+
+    let chars: Vec<char> = word.chars().collect();
+    let mut total: i32 = 0;
+    for (i, c) in chars.iter().enumerate() {
+        let current: i32 = value(*c);
+        match chars.get(i + 1) {
+            Some(next) if value(*next) > current => total -= current,
+            _ => total += current,
+        }
+    }
+
+    // Only accept numerals written the canonical way, so "iiv" or "vx" aren't numbers
+    if !(1..=30).contains(&total) || arabic_to_roman(total as u32) != word {
+        return None;
+    }
+    Some(total as u32)
+}
+
+fn arabic_to_roman(mut number: u32) -> String {
+    let numerals: [(u32, &str); 5] = [(10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i")];
+    let mut roman: String = String::new();
+
+    for (value, numeral) in numerals {
+        while number >= value {
+            roman.push_str(numeral);
+            number -= value;
+        }
+    }
+    roman
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_name_folds_spellings_together() {
+        assert_eq!(normalize_name("FINAL FANTASY® VII"), "final fantasy 7");
+        assert_eq!(normalize_name("Final Fantasy 7"), "final fantasy 7");
+        assert_eq!(normalize_name("Pokémon Légendes"), "pokemon legendes");
+        assert_eq!(normalize_name("Assassin’s Creed"), "assassins creed");
+        assert_eq!(normalize_name("  Half-Life: 2 "), "half life 2");
+    }
+
+    #[test]
+    fn only_canonical_roman_numerals_are_numbers() {
+        assert_eq!(roman_to_arabic("iv"), Some(4));
+        assert_eq!(roman_to_arabic("xix"), Some(19));
+        assert_eq!(roman_to_arabic("xxx"), Some(30));
+        assert_eq!(roman_to_arabic("iiv"), None);
+        assert_eq!(roman_to_arabic("vx"), None);
+        assert_eq!(roman_to_arabic("xxxi"), None);
+        assert_eq!(roman_to_arabic("vixen"), None);
+    }
+
+    #[test]
+    fn words_match_despite_typos_and_prefixes() {
+        assert_eq!(edit_distance("witcher", "wticher"), 1);
+        assert_eq!(word_similarity("witcher", "witcher"), 1.0);
+        assert_eq!(word_similarity("wticher", "witcher"), 0.7);
+        assert!(word_similarity("witch", "witcher") > 0.75);
+        assert_eq!(word_similarity("gta", "witcher"), 0.0);
+    }
+}
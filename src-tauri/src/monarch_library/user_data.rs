@@ -0,0 +1,190 @@
+/*
+* User data for library games, such as tags, favorites and hidden games.
+*
+* Kept in user_data.json rather than library.json so that refreshing the library
+* never overwrites it. The data is applied on top of games whenever they're read.
+*/
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use tracing::{error, warn};
+
+use crate::monarch_games::monarchgame::MonarchGame;
+use crate::monarch_utils::monarch_fs::{
+    backup_file, get_user_data_json_path, path_exists, write_json_content,
+};
+use crate::monarch_utils::monarch_state::MONARCH_STATE;
+
+// User data is read from disk once and kept in memory after that.
+static USER_DATA: Lazy<Mutex<Option<HashMap<String, UserGameData>>>> =
+    Lazy::new(|| Mutex::new(None));
+
+/// What the user has set for a game.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct UserGameData {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub favorite: bool,
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+/// Sets tags, favorite and hidden of games from what's stored in user_data.json.
+pub fn apply_user_data(games: &mut [MonarchGame]) {
+    let mut lock = lock_user_data();
+    let user_data = loaded_user_data(&mut lock);
+
+    for game in games.iter_mut() {
+        let data: UserGameData = user_data.get(&game.id).cloned().unwrap_or_default();
+        game.tags = data.tags;
+        game.favorite = data.favorite;
+        game.hidden = data.hidden;
+    }
+}
+
+/// Adds tags to games.
+pub fn add_tags(game_ids: &[String], tags: &[String]) -> Result<()> {
+    let tags: Vec<String> = clean_tags(tags);
+
+    update_games(game_ids, |data| {
+        for tag in tags.iter() {
+            if !data.tags.contains(tag) {
+                data.tags.push(tag.clone());
+            }
+        }
+        data.tags.sort();
+    })
+    .with_context(|| "user_data::add_tags() -> ")
+}
+
+/// Removes tags from games.
+pub fn remove_tags(game_ids: &[String], tags: &[String]) -> Result<()> {
+    let tags: Vec<String> = clean_tags(tags);
+
+    update_games(game_ids, |data| data.tags.retain(|tag| !tags.contains(tag)))
+        .with_context(|| "user_data::remove_tags() -> ")
+}
+
+/// Marks or unmarks games as favorites.
+pub fn set_favorite(game_ids: &[String], favorite: bool) -> Result<()> {
+    update_games(game_ids, |data| data.favorite = favorite)
+        .with_context(|| "user_data::set_favorite() -> ")
+}
+
+/// Hides or unhides games.
+pub fn set_hidden(game_ids: &[String], hidden: bool) -> Result<()> {
+    update_games(game_ids, |data| data.hidden = hidden)
+        .with_context(|| "user_data::set_hidden() -> ")
+}
+
+/// Returns every tag in use, sorted alphabetically.
+pub fn get_tags() -> Vec<String> {
+    let mut lock = lock_user_data();
+    loaded_user_data(&mut lock)
+        .values()
+        .flat_map(|data| data.tags.iter().cloned())
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .collect()
+}
+
+/// Applies a change to the user data of games, then saves it and updates the library state.
+fn update_games(game_ids: &[String], change: impl Fn(&mut UserGameData)) -> Result<()> {
+    {
+        let mut lock = lock_user_data();
+        let user_data = loaded_user_data(&mut lock);
+
+        for id in game_ids {
+            let data: &mut UserGameData = user_data.entry(id.clone()).or_default();
+            change(data);
+
+            if *data == UserGameData::default() {
+                user_data.remove(id);
+            }
+        }
+
+        write_user_data(user_data).with_context(|| "user_data::update_games() -> ")?;
+    }
+
+    let mut games: Vec<MonarchGame>;
+    unsafe {
+        games = MONARCH_STATE.get_library_games();
+    }
+    games.retain(|game| game_ids.contains(&game.id));
+    apply_user_data(&mut games);
+
+    for game in games.iter() {
+        unsafe {
+            MONARCH_STATE
+                .update_game(game)
+                .with_context(|| "user_data::update_games() -> ")?;
+        }
+    }
+    Ok(())
+}
+
+/// Trims tags and drops empty or repeated ones.
+fn clean_tags(tags: &[String]) -> Vec<String> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|tag| tag.trim()) {
+        if !tag.is_empty() && !cleaned.iter().any(|t| t == tag) {
+            cleaned.push(tag.to_string());
+        }
+    }
+    cleaned
+}
+
+/// Returns the in memory user data, reading it from disk if it hasn't been already.
+fn loaded_user_data<'a>(
+    lock: &'a mut MutexGuard<'static, Option<HashMap<String, UserGameData>>>,
+) -> &'a mut HashMap<String, UserGameData> {
+    lock.get_or_insert_with(|| match read_user_data() {
+        Ok(user_data) => user_data,
+        Err(e) => {
+            error!("user_data::loaded_user_data() -> {e:#}");
+
+            // Starting fresh would overwrite the user data on the next write, so it's kept aside
+            match backup_file(&get_user_data_json_path()) {
+                Ok(backup) => warn!("Unreadable user data moved to: {}", backup.display()),
+                Err(e) => error!("user_data::loaded_user_data() -> {e:#}"),
+            }
+            HashMap::new()
+        }
+    })
+}
+
+fn lock_user_data() -> MutexGuard<'static, Option<HashMap<String, UserGameData>>> {
+    USER_DATA
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Reads user_data.json, nothing is returned if the file doesn't exist yet.
+fn read_user_data() -> Result<HashMap<String, UserGameData>> {
+    let path: PathBuf = get_user_data_json_path();
+    if !path_exists(&path) {
+        return Ok(HashMap::new());
+    }
+
+    let content: String = fs::read_to_string(&path).with_context(|| {
+        format!(
+            "user_data::read_user_data() Error reading: {file} | Err: ",
+            file = path.display()
+        )
+    })?;
+    serde_json::from_str(&content)
+        .with_context(|| "user_data::read_user_data() Failed to parse user data! | Err: ")
+}
+
+fn write_user_data(user_data: &HashMap<String, UserGameData>) -> Result<()> {
+    let path: PathBuf = get_user_data_json_path();
+    write_json_content(json!(user_data), &path)
+        .with_context(|| "user_data::write_user_data() -> ")
+}
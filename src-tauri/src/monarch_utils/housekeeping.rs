@@ -12,9 +12,15 @@ use std::thread::sleep;
 use std::time::SystemTime;
 use std::{fs, time::Duration};
 use sysinfo::{System, SystemExt};
+use tauri::{AppHandle, Emitter};
 use tracing::{error, info};
 
 use super::monarch_fs::get_resources_cache;
+use super::monarch_settings::get_settings_state;
+use crate::monarch_games::{monarch_client, steam_client};
+
+/// How often games installed through SteamCMD are checked for updates.
+const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(6 * 3600);
 
 /// Runs HouseKeeper loop on seperate thread
 pub fn start() {
@@ -38,6 +44,28 @@ pub fn start() {
     });
 }
 
+/// Checks games installed through SteamCMD for updates every UPDATE_CHECK_INTERVAL on a
+/// seperate thread. Sends the ids of games with an update to the frontend as updates-available.
+pub fn start_update_checks(handle: AppHandle) {
+    thread::spawn(move || loop {
+        if get_settings_state().steam.manage && steam_client::is_installed() {
+            info!("Monarch Housekeeper: Checking for game updates...");
+
+            match monarch_client::check_for_updates(&handle) {
+                Ok(ids) => {
+                    info!("Games with updates available: {:?}", ids);
+                    if let Err(e) = handle.emit("updates-available", &ids) {
+                        error!("housekeeping::start_update_checks() Failed to emit updates-available! | Err: {e}");
+                    }
+                }
+                Err(e) => error!("housekeeping::start_update_checks() -> {e}"),
+            }
+        }
+
+        sleep(UPDATE_CHECK_INTERVAL);
+    });
+}
+
 /// Checks if system usage is sufficiently low to clear resources.
 /// Currently only checks a certain level of CPU usage, will possibly update later
 /// to check more metrics such as disk usage, memory, etc...
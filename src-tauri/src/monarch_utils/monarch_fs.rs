@@ -2,6 +2,7 @@ use anyhow::{Context, Result};
 use regex::Regex;
 use serde_json::Value;
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{fs, process::exit};
 use tracing::{error, info, warn};
 
@@ -132,6 +133,42 @@ pub fn get_collections_json_path() -> PathBuf {
     path.join("collections.json")
 }
 
+/// Returns path to play_history.json
+pub fn get_play_history_json_path() -> PathBuf {
+    let path: PathBuf = get_monarch_home();
+    path.join("play_history.json")
+}
+
+/// Returns path to user_data.json
+pub fn get_user_data_json_path() -> PathBuf {
+    let path: PathBuf = get_monarch_home();
+    path.join("user_data.json")
+}
+
+/// Returns path to merges.json
+pub fn get_merges_json_path() -> PathBuf {
+    let path: PathBuf = get_monarch_home();
+    path.join("merges.json")
+}
+
+/// Returns path to download_queue.json
+pub fn get_download_queue_json_path() -> PathBuf {
+    let path: PathBuf = get_monarch_home();
+    path.join("download_queue.json")
+}
+
+/// Returns path to the cached app info of a Steam game
+pub fn get_appinfo_cache_path(id: &str) -> PathBuf {
+    let path: PathBuf = get_monarch_home();
+    path.join("appinfo").join(format!("{id}.json"))
+}
+
+/// Returns path to the Proton prefix of a game played through Proton outside of Steam
+pub fn get_compatdata_path(id: &str) -> PathBuf {
+    let path: PathBuf = get_monarch_home();
+    path.join("compatdata").join(id)
+}
+
 /// Write JSON to file
 pub fn write_json_content(content: Value, path: &Path) -> Result<()> {
     fs::write(path, serde_json::to_string_pretty(&content).unwrap()) // TODO: Remove unwrap for better error handling
@@ -150,6 +187,35 @@ pub fn create_dir(path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Copies the contents of a directory into another, replacing files that are already there
+pub fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    create_dir(to).with_context(|| "monarch_fs::copy_dir() -> ")?;
+
+    for entry in fs::read_dir(from).with_context(|| format!("monarch_fs::copy_dir() Failed to read directory: {dir} | Err: ", dir = from.display()))? {
+        let entry = entry.with_context(|| "monarch_fs::copy_dir() Failed to read directory entry! | Err: ")?;
+        let destination: PathBuf = to.join(entry.file_name());
+
+        if entry.path().is_dir() {
+            copy_dir(&entry.path(), &destination).with_context(|| "monarch_fs::copy_dir() -> ")?;
+        } else {
+            fs::copy(entry.path(), &destination).with_context(|| format!("monarch_fs::copy_dir() Failed to copy: {file} | Err: ", file = entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Moves a file out of the way to a backup next to it, named after it and the current time.
+/// Returns the path of the backup.
+pub fn backup_file(path: &Path) -> Result<PathBuf> {
+    let time: u64 = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{time}.bak"));
+
+    let backup: PathBuf = path.with_file_name(name);
+    fs::rename(path, &backup).with_context(|| format!("monarch_fs::backup_file() Failed to back up: {file} | Err: ", file = path.display()))?;
+    Ok(backup)
+}
+
 /*
 ---------- Functions related to storing in resources dir ----------
 */
@@ -189,6 +255,15 @@ pub fn generate_library_image_path(name: &str) -> PathBuf {
     path.join(filename)
 }
 
+/// Returns path to store artwork next to a thumbnail, e.g. Name.jpg -> Name_artwork.jpg
+pub fn generate_artwork_path(thumbnail_path: &Path) -> PathBuf {
+    let stem: String = thumbnail_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    thumbnail_path.with_file_name(format!("{stem}_artwork.jpg"))
+}
+
 /// Generates a filename without any special characters or spaces
 fn generate_image_filename(name: &str) -> String {
     let mut filename: String = String::from(name);
@@ -61,3 +61,8 @@ pub fn get_log_file() -> PathBuf {
     log_path = log_path.join("monarch.log");
     log_path
 }
+
+/// Path to the log of a single game, containing output of the game and its hooks.
+pub fn get_game_log_path(id: &str) -> PathBuf {
+    get_log_dir().join("games").join(format!("{id}.log"))
+}
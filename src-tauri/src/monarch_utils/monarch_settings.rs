@@ -1,6 +1,7 @@
 use anyhow::{bail, Context, Result};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
@@ -8,6 +9,8 @@ use toml::Table;
 use tracing::error;
 
 use super::monarch_fs::{create_dir, generate_monarch_home, get_settings_path, path_exists};
+use crate::monarch_games::launch_hooks::LaunchHooks;
+use crate::monarch_games::launch_wrappers::LaunchWrappers;
 use crate::monarch_games::monarch_client::generate_default_folder;
 
 // Create a global variable containing the current state of settings according to Monarch backend.
@@ -43,6 +46,38 @@ pub struct QuicklaunchSettings {
     pub size: String,
 }
 
+/// Which sections are shown on the home page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HomeSettings {
+    pub continue_playing: bool,
+    pub installed_never_launched: bool,
+    pub forgotten_favorites: bool,
+    pub quick_session: bool,
+    pub games_per_section: usize,
+}
+
+impl Default for HomeSettings {
+    fn default() -> Self {
+        Self {
+            continue_playing: true,
+            installed_never_launched: true,
+            forgotten_favorites: true,
+            quick_session: true,
+            games_per_section: 4,
+        }
+    }
+}
+
+/// Defaults used when launching games, games can override them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchSettings {
+    pub wrappers: LaunchWrappers,
+    pub env: HashMap<String, String>, // Set for every game, games can override them
+    pub hooks: LaunchHooks,
+}
+
 /// Struct for storing a persistent state of settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Settings {
@@ -50,6 +85,10 @@ pub struct Settings {
     pub quicklaunch: QuicklaunchSettings,
     pub steam: LauncherSettings,
     pub epic: LauncherSettings,
+    #[serde(default)] // Missing in settings.toml from older versions
+    pub home: HomeSettings,
+    #[serde(default)]
+    pub launch: LaunchSettings,
 }
 
 // TODO: Redo this implementation to make sure it doesn't panic
@@ -101,6 +140,8 @@ impl Default for Settings {
             quicklaunch,
             steam,
             epic,
+            home: HomeSettings::default(),
+            launch: LaunchSettings::default(),
         }
     }
 }
@@ -1,4 +1,6 @@
 use crate::monarch_games::monarchgame::MonarchGame;
+use crate::monarch_library::search::mark_index_outdated;
+use crate::monarch_library::user_data::apply_user_data;
 use anyhow::{bail, Result};
 use once_cell::sync::Lazy;
 
@@ -22,8 +24,11 @@ impl MonarchState {
     /// For setting known library games.
     /// Should probably only be run when refreshing library.
     pub fn set_library_games(&mut self, games: &[MonarchGame]) {
+        mark_index_outdated();
+
         if self.library_games.is_empty() {
             self.library_games = games.to_vec();
+            apply_user_data(&mut self.library_games);
             return;
         }
 
@@ -52,6 +57,7 @@ impl MonarchState {
         }
 
         self.library_games = new_games;
+        apply_user_data(&mut self.library_games);
     }
 
     /// Update a game.
@@ -61,6 +67,7 @@ impl MonarchState {
         for (i, self_game) in self.library_games.iter_mut().enumerate() {
             if self_game.id == game.id {
                 self.library_games[i] = game.clone();
+                mark_index_outdated();
                 return Ok(());
             }
         }
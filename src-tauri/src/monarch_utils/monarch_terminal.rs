@@ -9,7 +9,7 @@
 use super::monarch_windows::MiniWindow;
 use anyhow::{bail, Context, Result};
 use once_cell::sync::Lazy;
-use portable_pty::{native_pty_system, CommandBuilder, PtyPair, PtySize};
+use portable_pty::{native_pty_system, CommandBuilder, ExitStatus, PtyPair, PtySize};
 use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Read, Write};
 use std::sync::Arc;
@@ -36,25 +36,6 @@ pub async fn run_in_terminal(
     command: &str,
     env_vars: Option<HashMap<&str, &str>>,
 ) -> Result<()> {
-    info!("Starting Monarch terminal...");
-
-    let pty_system = native_pty_system();
-    let pair = pty_system.openpty(PtySize {
-        rows: 80,
-        cols: 160,
-        // Not all systems support pixel_width, pixel_height,
-        // but it is good practice to set it to something
-        // that matches the size of the selected font.  That
-        // is more complex than can be shown here in this
-        // brief example though!
-        pixel_width: 0,
-        pixel_height: 0,
-    })?;
-
-    // Read and parse output from the pty with reader
-    let reader = pair.master.try_clone_reader().unwrap();
-    let writer = pair.master.take_writer().unwrap();
-
     let term_command: String = command.to_string();
 
     // Spawn a shell into the pty
@@ -91,6 +72,31 @@ pub async fn run_in_terminal(
         cmd
     };
 
+    run_command_in_terminal(handle, cmd).await.map(|_| ())
+}
+
+/// Run a program directly, without a shell, and display it to the user in a custom terminal window.
+/// Returns the exit status of the program.
+pub async fn run_command_in_terminal(handle: &AppHandle, cmd: CommandBuilder) -> Result<ExitStatus> {
+    info!("Starting Monarch terminal...");
+
+    let pty_system = native_pty_system();
+    let pair = pty_system.openpty(PtySize {
+        rows: 80,
+        cols: 160,
+        // Not all systems support pixel_width, pixel_height,
+        // but it is good practice to set it to something
+        // that matches the size of the selected font.  That
+        // is more complex than can be shown here in this
+        // brief example though!
+        pixel_width: 0,
+        pixel_height: 0,
+    })?;
+
+    // Read and parse output from the pty with reader
+    let reader = pair.master.try_clone_reader().unwrap();
+    let writer = pair.master.take_writer().unwrap();
+
     let mut child = pair
         .slave
         .spawn_command(cmd)
@@ -109,7 +115,7 @@ pub async fn run_in_terminal(
         error!("monarch_terminal::run_in_terminal() -> {e}");
     }
 
-    let exit_status= child.wait().with_context(|| "Something went wrong while waiting for child process to finish! | Err: ")?;
+    let exit_status: ExitStatus = child.wait().with_context(|| "Something went wrong while waiting for child process to finish! | Err: ")?;
     info!("Child exited.");
     info!("Child process exited with status: {:?}", exit_status);
 
@@ -117,7 +123,7 @@ pub async fn run_in_terminal(
         error!("monarch_terminal::run_in_terminal() -> {e}");
     }
 
-    Ok(())
+    Ok(exit_status)
 }
 
 /// Creates a new Monarch terminal window, meant to be called from frontend.
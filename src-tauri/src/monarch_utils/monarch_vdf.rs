@@ -2,7 +2,7 @@
     This file is for parsing Valve's .vdf (Valve Data Format) format.
     It is used for reading content related to steam such as the users installed library, library locations in the filesystem, etc.
 */
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use keyvalues_serde;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -147,3 +147,170 @@ fn get_games_from_manifest_files(path: &Path) -> Result<Vec<String>> {
     info!("Found IDs: {:?}", games);
     Ok(games)
 }
+
+/// Text KeyValues as printed by SteamCMD, whose keys can hold either a string or nested keys.
+/// Unlike a map, keys keep their order and may repeat.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyValues {
+    Value(String),
+    Object(Vec<(String, KeyValues)>),
+}
+
+impl KeyValues {
+    /// Returns the first value of a key, ignoring case like Steam does.
+    pub fn get(&self, key: &str) -> Option<&KeyValues> {
+        self.entries()
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the string of a key, None if it's missing or holds nested keys.
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(KeyValues::as_str)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            KeyValues::Value(value) => Some(value),
+            KeyValues::Object(_) => None,
+        }
+    }
+
+    /// Returns the nested keys, empty for a string.
+    pub fn entries(&self) -> &[(String, KeyValues)] {
+        match self {
+            KeyValues::Value(_) => &[],
+            KeyValues::Object(entries) => entries,
+        }
+    }
+}
+
+/// Parses text KeyValues into an object holding its top level keys.
+/// Conditions such as [$WIN32] are ignored.
+pub fn parse_keyvalues(text: &str) -> Result<KeyValues> {
+    let tokens: Vec<Token> = tokenize(text).with_context(|| "monarch_vdf::parse_keyvalues() -> ")?;
+    let mut tokens = tokens.into_iter();
+
+    let root: KeyValues =
+        parse_object(&mut tokens, false).with_context(|| "monarch_vdf::parse_keyvalues() -> ")?;
+    Ok(root)
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    String(String),
+    Open,
+    Close,
+}
+
+fn parse_object(tokens: &mut impl Iterator<Item = Token>, nested: bool) -> Result<KeyValues> {
+    let mut entries: Vec<(String, KeyValues)> = Vec::new();
+
+    loop {
+        let key: String = match tokens.next() {
+            Some(Token::String(key)) => key,
+            Some(Token::Close) if nested => return Ok(KeyValues::Object(entries)),
+            None if !nested => return Ok(KeyValues::Object(entries)),
+            Some(token) => bail!("monarch_vdf::parse_object() | Err: Expected a key, found: {token:?}"),
+            None => bail!("monarch_vdf::parse_object() | Err: Missing closing brace!"),
+        };
+
+        let value: KeyValues = match tokens.next() {
+            Some(Token::String(value)) => KeyValues::Value(value),
+            Some(Token::Open) => parse_object(tokens, true)?,
+            _ => bail!("monarch_vdf::parse_object() | Err: Missing value of: {key}"),
+        };
+        entries.push((key, value));
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '"' => {
+                let mut string: String = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // Paths often hold single backslashes, so only quotes and backslashes
+                        // are unescaped
+                        Some('\\') => match chars.next_if(|c| *c == '"' || *c == '\\') {
+                            Some(escaped) => string.push(escaped),
+                            None => string.push('\\'),
+                        },
+                        Some(c) => string.push(c),
+                        None => bail!("monarch_vdf::tokenize() | Err: Unterminated string!"),
+                    }
+                }
+                tokens.push(Token::String(string));
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            '[' => {
+                while chars.next().is_some_and(|c| c != ']') {}
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut string: String = String::from(c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"{}\"".contains(*c)) {
+                    string.push(c);
+                }
+                tokens.push(Token::String(string));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_keyvalues_reads_nested_objects() {
+        let text: &str = r#"
+            "AppState"
+            {
+                "appid"     "440"
+                // Comments and conditions are skipped
+                "InstallDir" "C:\Games\Team Fortress 2"  [$WIN32]
+                "name" "Say \"Hi\" \\ bye"
+                unquoted value
+                "UserConfig" { "BetaKey" "prerelease" }
+            }
+        "#;
+        let root: KeyValues = parse_keyvalues(text).unwrap();
+        let app: &KeyValues = root.get("appstate").unwrap();
+
+        assert_eq!(app.get_str("AppID"), Some("440"));
+        assert_eq!(app.get_str("installdir"), Some(r"C:\Games\Team Fortress 2"));
+        assert_eq!(app.get_str("name"), Some(r#"Say "Hi" \ bye"#));
+        assert_eq!(app.get_str("unquoted"), Some("value"));
+        assert_eq!(
+            app.get("userconfig").and_then(|c| c.get_str("betakey")),
+            Some("prerelease")
+        );
+        assert_eq!(app.get_str("UserConfig"), None);
+        assert_eq!(app.entries().len(), 5);
+    }
+
+    #[test]
+    fn parse_keyvalues_rejects_broken_text() {
+        for text in [
+            r#""app" { "id" "1""#,
+            r#""app" { "id" "1" } }"#,
+            r#""app" { "id" }"#,
+            r#""key" "unterminated"#,
+        ] {
+            assert!(parse_keyvalues(text).is_err(), "Parsed broken text: {text}");
+        }
+        assert!(parse_keyvalues("").unwrap().entries().is_empty());
+    }
+}
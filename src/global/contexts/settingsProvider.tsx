@@ -43,6 +43,23 @@ const defaultSettings = {
     enabled: true,
     size: 'medium',
   },
+  home: {
+    continue_playing: true,
+    installed_never_launched: true,
+    forgotten_favorites: true,
+    quick_session: true,
+    games_per_section: 4,
+  },
+  launch: {
+    wrappers: {},
+    env: {},
+    hooks: {
+      pre_launch: '',
+      post_exit: '',
+      timeout: 0,
+      abort_on_failure: true,
+    },
+  },
 };
 
 const initialState: SettingsContextType = {
@@ -8,6 +8,138 @@ export type MonarchGame = {
   store_page: string;
   compatibility: string;
   launch_args: string;
+  summary?: string;
+  artwork_path?: string;
+  genres?: string[];
+  developer?: string;
+  publisher?: string;
+  release_date?: string;
+  igdb_id?: number | null;
+  tags?: string[];
+  favorite?: boolean;
+  hidden?: boolean;
+  installations?: Installation[];
+  launch_profiles?: LaunchProfile[];
+  default_profile?: string;
+  wrappers?: LaunchWrappers;
+  working_dir?: string;
+  env?: Record<string, string>;
+  hooks?: LaunchHooks;
+  show_terminal?: boolean;
+  install_dir?: string;
+  platform_override?: string;
+  update_available?: boolean;
+  beta_branch?: string;
+  pinned_manifests?: Record<string, string>;
+};
+
+export type CrashReport = {
+  game_id: string;
+  game_name: string;
+  crashed_at: number;
+  reason: string;
+  exit_code: number | null;
+  signal: string | null;
+  session_seconds: number;
+  runner: string;
+  runner_version: string;
+  command: string;
+  env: Record<string, string>;
+  output: string;
+  proton_log: string;
+  report_path: string;
+};
+
+export type QueuedDownload = {
+  id: string;
+  name: string;
+  platform: string;
+  platform_id: string;
+  kind: 'install' | 'update' | 'pin';
+  state: 'queued' | 'downloading' | 'failed';
+  error: string;
+  progress: number;
+  install_dir: string;
+  platform_override: string;
+  compatibility: string;
+  branch: string;
+  depot_id: string;
+  manifest_id: string;
+  added_at: number;
+};
+
+export type DownloadProgress = {
+  id: string;
+  platform_id: string;
+  state: string;
+  progress: number;
+  downloaded: number;
+  total: number;
+  speed: number;
+  eta: number | null;
+};
+
+export type SteamGuardPrompt = 'two_factor' | 'email' | 'mobile';
+
+export type LogChunk = {
+  content: string;
+  offset: number;
+};
+
+export type LaunchHooks = {
+  pre_launch: string;
+  post_exit: string;
+  timeout: number;
+  abort_on_failure: boolean;
+};
+
+export type LaunchSpec = {
+  program: string;
+  args: string[];
+  env: Record<string, string>;
+  cwd: string | null;
+};
+
+export type LaunchWrappers = {
+  gamescope?: {
+    enabled: boolean;
+    width: number;
+    height: number;
+    refresh: number;
+    upscaler: string;
+    hdr: boolean;
+    fullscreen: boolean;
+  } | null;
+  mangohud?: {
+    enabled: boolean;
+    config: string;
+  } | null;
+  gamemode?: boolean | null;
+  obs_gamecapture?: boolean | null;
+};
+
+export type LaunchProfile = {
+  name: string;
+  executable_path: string;
+  launch_args: string;
+  working_dir: string;
+  env: Record<string, string>;
+  compatibility: string;
+  pre_launch: string;
+  post_exit: string;
+};
+
+export type Installation = {
+  id: string;
+  name: string;
+  platform: string;
+  platform_id: string;
+  executable_path: string;
+};
+
+export type DuplicateGroup = {
+  games: MonarchGame[];
+  reasons: string[];
 };
 
 export type Result = {
@@ -20,6 +152,9 @@ export type Collection = {
   id: string;
   name: string;
   gameIds: string[];
+  rule?: string | null;
+  order?: number;
+  parentId?: string | null;
 };
 
 type LauncherType = {
@@ -45,6 +180,25 @@ export type Settings = {
     enabled: boolean;
     size: string;
   };
+  home: {
+    continue_playing: boolean;
+    installed_never_launched: boolean;
+    forgotten_favorites: boolean;
+    quick_session: boolean;
+    games_per_section: number;
+  };
+  launch: {
+    wrappers: LaunchWrappers;
+    env: Record<string, string>;
+    hooks: LaunchHooks;
+  };
+};
+
+export type RecommendationSection = {
+  id: string;
+  title: string;
+  explanation: string;
+  games: MonarchGame[];
 };
 
 export type ProtonVersion = {
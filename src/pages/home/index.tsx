@@ -4,6 +4,8 @@ import fallback from '@assets/fallback.jpg';
 import { invoke } from '@tauri-apps/api/core';
 import * as React from 'react';
 
+import type { RecommendationSection } from '@global/types';
+
 const gridStyle: React.CSSProperties = {
   display: 'flex',
   flexWrap: 'wrap',
@@ -13,7 +15,7 @@ const gridStyle: React.CSSProperties = {
 };
 
 const Home = () => {
-  const [games, setGames] = React.useState<any[]>([]);
+  const [sections, setSections] = React.useState<RecommendationSection[]>([]);
   const [loading, setLoading] = React.useState(true);
   const [error, setError] = React.useState<string | null>(null);
 
@@ -22,9 +24,11 @@ const Home = () => {
       setLoading(true);
       setError(null);
       try {
-        const result = await invoke<any[]>('get_home_recomendations');
+        const result = await invoke<RecommendationSection[]>(
+          'get_home_recomendations',
+        );
         // Defensive: fallback to [] if not an array
-        setGames(Array.isArray(result) ? result : []);
+        setSections(Array.isArray(result) ? result : []);
       } catch (err: any) {
         setError('Failed to load recommendations.');
       } finally {
@@ -40,25 +44,35 @@ const Home = () => {
         Welcome to Monarch
       </h1>
       <p style={{ textAlign: 'center', color: '#aaa', fontSize: '1.2rem' }}>
-        Your personal game library and launcher. Here are some featured games:
+        Your personal game library and launcher.
       </p>
       {loading && <p style={{ textAlign: 'center' }}>Loading recommendations...</p>}
       {error && <p style={{ textAlign: 'center', color: 'red' }}>{error}</p>}
-      <div style={gridStyle}>
-        {games.map((game) => (
-          <GameCard
-            key={game.id}
-            id={game.id}
-            platformId={game.platform_id}
-            executablePath={game.executable_path}
-            name={game.name}
-            platform={game.platform}
-            thumbnailPath={game.thumbnail_path || fallback}
-            storePage={game.store_page}
-            cardWidth="24rem"
-          />
-        ))}
-      </div>
+      {sections.map((section) => (
+        <section key={section.id} style={{ marginTop: '2rem' }}>
+          <h2 style={{ textAlign: 'center', margin: '0.5rem 0' }}>
+            {section.title}
+          </h2>
+          <p style={{ textAlign: 'center', color: '#aaa' }}>
+            {section.explanation}
+          </p>
+          <div style={gridStyle}>
+            {section.games.map((game) => (
+              <GameCard
+                key={game.id}
+                id={game.id}
+                platformId={game.platform_id}
+                executablePath={game.executable_path}
+                name={game.name}
+                platform={game.platform}
+                thumbnailPath={game.thumbnail_path || fallback}
+                storePage={game.store_page}
+                cardWidth="24rem"
+              />
+            ))}
+          </div>
+        </section>
+      ))}
     </Page>
   );
 };
@@ -28,6 +28,13 @@ const Title = styled.p`
   overflow: hidden;
 `;
 
+const Profiles = styled.div`
+  display: flex;
+  flex-wrap: wrap;
+  gap: 0.5rem;
+  margin-top: 0.5rem;
+`;
+
 const Thumbnail = styled.img`
   height: 3rem;
   width: 6rem;
@@ -43,9 +50,9 @@ type Props = {
 const GameButton = ({ game, isFocused = false, onFocus }: Props) => {
   const wrapperRef = React.useRef<HTMLDivElement>(null);
 
-  const handleLaunch = React.useCallback(async () => {
+  const handleLaunch = React.useCallback(async (profile?: string) => {
     try {
-      await invoke('launch_game', { game });
+      await invoke('launch_game', { game, profile });
       // TODO: Close window instance
     } catch (err) {
       await dialog.message(`An error has occured: ${err}`, {
@@ -87,12 +94,27 @@ const GameButton = ({ game, isFocused = false, onFocus }: Props) => {
       <StyledButton
         type="button"
         variant="secondary"
-        onClick={handleLaunch}
+        onClick={() => handleLaunch()}
         fullWidth
       >
         <Thumbnail src={imageSrc} alt="game thumnbail" />
         <Title>{game.name}</Title>
       </StyledButton>
+      {game.launch_profiles && game.launch_profiles.length > 0 && (
+        <Profiles>
+          {game.launch_profiles.map((profile) => (
+            <Button
+              key={profile.name}
+              type="button"
+              variant="secondary"
+              onClick={() => handleLaunch(profile.name)}
+            >
+              {profile.name}
+              {profile.name === game.default_profile && ' (default)'}
+            </Button>
+          ))}
+        </Profiles>
+      )}
     </ButtonWrapper>
   );
 };